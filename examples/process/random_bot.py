@@ -0,0 +1,48 @@
+#!/usr/bin/env python3
+# Plays random legal moves over the wasmthello line protocol, see the README.
+# Run it with `ProcessPlayer::spawn(&mut Command::new("examples/process/random_bot.py"))`.
+import random
+import sys
+
+DIRECTIONS = [(0, -1), (0, 1), (-1, 0), (1, 0)]
+DIAGONALS = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+
+
+def legal_moves(size, spaces, me, directions):
+    def at(x, y):
+        return spaces[y * size + x]
+
+    opponent = "W" if me == "B" else "B"
+    moves = []
+    for y in range(size):
+        for x in range(size):
+            if at(x, y) != ".":
+                continue
+            for dx, dy in directions:
+                cx, cy, seen = x + dx, y + dy, False
+                while 0 <= cx < size and 0 <= cy < size and at(cx, cy) == opponent:
+                    cx, cy, seen = cx + dx, cy + dy, True
+                if seen and 0 <= cx < size and 0 <= cy < size and at(cx, cy) == me:
+                    moves.append("%s%d" % (chr(ord("a") + x), y + 1))
+                    break
+    return moves
+
+
+def send(line):
+    print(line, flush=True)
+
+
+for line in sys.stdin:
+    command, _, argument = line.strip().partition(" ")
+    if command == "wasmthello":
+        send("ready random_bot.py")
+    elif command == "turn":
+        board, me, *rules = argument.split()
+        size, spaces = board.split(":")
+        spaces = spaces.replace("/", "")
+        directions = DIRECTIONS + (DIAGONALS if rules == ["standard"] else [])
+        send("play " + random.choice(legal_moves(int(size), spaces, me, directions)))
+    elif command == "draw":
+        send("decline")
+    elif command == "quit":
+        break
@@ -0,0 +1,77 @@
+use std::error::Error;
+use crate::game::{Game, Pos};
+use crate::referenceplayers::{negamax, DEFAULT_DEPTH, WIN_SCORE};
+use crate::solver::Solver;
+
+/// Names of the engines, as understood by `Engine::from_name`.
+pub const ENGINES: [&str; 3] = ["positional", "alphabeta", "solver"];
+
+/// Scores positions and moves for hints and game analysis. All engines use
+/// the scale of `AlphaBetaPlayer`'s evaluation: square weights and mobility
+/// while the game goes on, and 1000 per disc of margin once it is decided.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Engine {
+    /// Square weights and mobility of the position itself.
+    Positional,
+    /// Alpha-beta search to the given depth, as `AlphaBetaPlayer` plays.
+    AlphaBeta(usize),
+    /// Exact scores from the endgame solver when there are few enough empty
+    /// spaces, alpha-beta search otherwise.
+    Solver,
+}
+
+/// An engine's view of one legal move.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MoveEvaluation {
+    pub pos: Pos,
+    /// Score of the position after the move, for the player making it.
+    pub score: i32,
+    /// Discs the move flips.
+    pub flips: usize,
+    /// Legal moves the opponent has after the move.
+    pub opponent_mobility: usize,
+}
+
+impl Engine {
+    /// Creates an engine from its name in `ENGINES`, with an optional depth
+    /// for `alphabeta`, e.g. `alphabeta:6`.
+    pub fn from_name(spec: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(match spec.split_once(':').unwrap_or((spec, "")) {
+            ("positional", "") => Engine::Positional,
+            ("alphabeta", "") => Engine::AlphaBeta(DEFAULT_DEPTH),
+            ("alphabeta", depth) => match depth.parse().map_err(|err| format!("invalid depth {:?} for alphabeta: {}", depth, err))? {
+                0 => return Err("alphabeta depth must be at least 1".into()),
+                depth => Engine::AlphaBeta(depth),
+            },
+            ("solver", "") => Engine::Solver,
+            _ => return Err(format!("unknown engine {:?}, expected one of {}", spec, ENGINES.join(", ")).into()),
+        })
+    }
+
+    /// Score of `game` for the player to move.
+    pub fn evaluate<const N: usize>(&self, game: &Game<N>) -> i32 {
+        let mut game = game.clone();
+        match self {
+            Engine::Positional => negamax(&mut game, 0, -i32::MAX, i32::MAX),
+            Engine::AlphaBeta(depth) => negamax(&mut game, *depth, -i32::MAX, i32::MAX),
+            Engine::Solver => match Solver::new().solve(&game) {
+                Ok(solution) => WIN_SCORE * solution.score,
+                Err(_) => negamax(&mut game, DEFAULT_DEPTH, -i32::MAX, i32::MAX),
+            },
+        }
+    }
+
+    /// Every legal move of the player to move, best first.
+    pub fn evaluate_moves<const N: usize>(&self, game: &Game<N>) -> Vec<MoveEvaluation> {
+        let player = game.current_player();
+        let mut evaluations: Vec<MoveEvaluation> = game.legal_moves(player).into_iter().map(|pos| {
+            let flips = game.flips(pos).len();
+            let mut after = game.clone();
+            after.play(pos);
+            let opponent_mobility = after.legal_moves(after.current_player()).len();
+            MoveEvaluation { pos, score: -self.evaluate(&after), flips, opponent_mobility }
+        }).collect();
+        evaluations.sort_by_key(|evaluation| -evaluation.score);
+        evaluations
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How far an `Executor` has got with its items.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Index of the item that just finished.
+    pub item: usize,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Runs independent jobs, typically games, on a bounded number of threads.
+/// Every thread starts with its own share of the items and steals from the
+/// others when it runs out, so long games don't hold up the rest. Results are
+/// returned in the order of the items no matter which thread played them.
+pub struct Executor<'a> {
+    threads: usize,
+    progress: Option<Box<dyn Fn(Progress) + Sync + 'a>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(threads: usize) -> Self {
+        Self { threads: threads.max(1), progress: None }
+    }
+
+    /// Called after every finished item, from the thread that ran it.
+    pub fn on_progress(mut self, progress: impl Fn(Progress) + Sync + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Calls `job` for every item and returns the results in the order of the items.
+    pub fn run<T: Sync, R: Send>(&self, items: &[T], job: impl Fn(&T) -> R + Sync) -> Vec<R> {
+        let threads = self.threads.min(items.len());
+        if threads == 0 {
+            return Vec::new();
+        }
+        // Contiguous shares, so each thread mostly works on neighbouring items
+        let queues: Vec<Mutex<VecDeque<usize>>> = (0..threads)
+            .map(|t| Mutex::new((t * items.len() / threads..(t + 1) * items.len() / threads).collect()))
+            .collect();
+        let completed = AtomicUsize::new(0);
+        let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+        thread::scope(|scope| {
+            for own in 0..threads {
+                let (queues, completed, results, job) = (&queues, &completed, &results, &job);
+                scope.spawn(move || {
+                    while let Some(i) = next_item(queues, own) {
+                        let result = job(&items[i]);
+                        results.lock().unwrap()[i] = Some(result);
+                        let completed = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                        if let Some(progress) = &self.progress {
+                            progress(Progress { item: i, completed, total: items.len() });
+                        }
+                    }
+                });
+            }
+        });
+        results.into_inner().unwrap().into_iter().map(|r| r.expect("every item is run")).collect()
+    }
+}
+
+// Takes from the front of the thread's own queue, or else from the back of
+// the fullest other queue.
+fn next_item(queues: &[Mutex<VecDeque<usize>>], own: usize) -> Option<usize> {
+    if let Some(i) = queues[own].lock().unwrap().pop_front() {
+        return Some(i);
+    }
+    loop {
+        let victim = (0..queues.len())
+            .filter(|&t| t != own)
+            .max_by_key(|&t| queues[t].lock().unwrap().len())?;
+        let mut queue = queues[victim].lock().unwrap();
+        if queue.is_empty() {
+            // Emptied by its owner in the meantime, check whether any work is left at all
+            drop(queue);
+            if queues.iter().all(|q| q.lock().unwrap().is_empty()) {
+                return None;
+            }
+            continue;
+        }
+        return queue.pop_back();
+    }
+}
@@ -1,4 +1,13 @@
 use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use crate::rng::Rng;
+
+// Seeds the Zobrist keys, changing it changes every hash
+const ZOBRIST_SEED: u64 = 0x5a0b_7157_0000_0001;
+
+// Maps (N - 1, x, y) to where a rotation or mirroring of the board moves (x, y)
+type Symmetry = fn(usize, usize, usize) -> (usize, usize);
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Player {
@@ -7,7 +16,7 @@ pub enum Player {
 }
 
 impl Player {
-    fn flip(&self) -> Player {
+    pub(crate) fn flip(&self) -> Player {
         match *self {
             Player::White => Player::Black,
             Player::Black => Player::White
@@ -25,6 +34,19 @@ impl Player {
 pub struct Pos(usize,usize);
 
 impl Pos {
+    /// Column `x` and row `y`, counting from 0 in the top left corner.
+    pub fn new(x: usize, y: usize) -> Self {
+        Pos(x, y)
+    }
+
+    pub fn x(&self) -> usize {
+        self.0
+    }
+
+    pub fn y(&self) -> usize {
+        self.1
+    }
+
     pub fn to_offset(&self, board_size: usize) -> u8 {
         (self.0 + self.1 * board_size) as u8
     }
@@ -34,8 +56,106 @@ impl Pos {
     }
 }
 
+/// Algebraic notation: the column as a letter from `a`, then the row as a
+/// number from 1, e.g. `a1` for the top left corner and `h8` for the bottom
+/// right corner of an 8x8 board. Columns go up to `z`, enough for 26x26 boards.
+/// Columns past `z` are written as `x,y` from 0, as in older game records.
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 >= 26 {
+            return write!(f, "{},{}", self.0, self.1);
+        }
+        write!(f, "{}{}", (b'a' + self.0 as u8) as char, self.1 + 1)
+    }
+}
+
+impl FromStr for Pos {
+    type Err = Box<dyn Error>;
+
+    /// Parses algebraic notation, ignoring case. Doesn't check that the
+    /// position is on the board.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let column = chars.next().filter(char::is_ascii_alphabetic)
+            .ok_or_else(|| format!("{:?} doesn't start with a column letter", s))?;
+        let row: usize = chars.as_str().parse()
+            .map_err(|_| format!("{:?} doesn't end with a row number", s))?;
+        if row == 0 {
+            return Err(format!("{:?}: rows start at 1", s).into());
+        }
+        Ok(Pos((column.to_ascii_lowercase() as u8 - b'a') as usize, row - 1))
+    }
+}
+
+/// What a player does with its turn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Turn {
+    Move(Pos),
+    /// Offers the opponent a draw. If it declines, the player is asked again
+    /// and has to move or resign.
+    OfferDraw,
+    Resign,
+    /// Takes back moves until it is the player's turn again, with its own
+    /// last move undone. Only allowed in matches with `MatchConfig::allow_undo`.
+    Undo,
+}
+
 pub trait PlayerController<const N: usize> {
     fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>>;
+
+    /// Like `make_play`, but the player may also resign or offer a draw.
+    /// Plays the move from `make_play` by default.
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
+        self.make_play(game).map(Turn::Move)
+    }
+
+    /// The opponent, who is to move in `game`, offers a draw. Declines by default.
+    fn accept_draw(&mut self, _game: &Game<N>) -> bool {
+        false
+    }
+}
+
+/// How a game ended.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Ending {
+    /// Neither player could move.
+    Completed,
+    /// The player failed to produce a legal move and forfeited.
+    Misplay(Player),
+    Resignation(Player),
+    DrawAgreement,
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ending::Completed => write!(f, "completed"),
+            Ending::Misplay(player) => write!(f, "{:?} misplayed", player),
+            Ending::Resignation(player) => write!(f, "{:?} resigned", player),
+            Ending::DrawAgreement => write!(f, "draw by agreement"),
+        }
+    }
+}
+
+/// Which lines of tiles are flipped by a move.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Rules {
+    /// Only rows and columns are flipped.
+    #[default]
+    Orthogonal,
+    /// Rows, columns and diagonals are flipped, as in regular Othello.
+    Standard,
+}
+
+impl Rules {
+    fn directions(&self) -> &'static [Dir] {
+        match *self {
+            Rules::Orthogonal => &[Dir::Up, Dir::Down, Dir::Left, Dir::Right],
+            Rules::Standard => &[Dir::Up, Dir::Down, Dir::Left, Dir::Right,
+                                 Dir::UpLeft, Dir::UpRight, Dir::DownLeft, Dir::DownRight],
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -43,16 +163,24 @@ enum Dir {
     Up,
     Down,
     Left,
-    Right
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl Dir {
     fn delta_pos(&self) -> (isize, isize) {
         match *self {
-            Dir::Up    => (0, -1),
-            Dir::Down  => (0,  1),
-            Dir::Left  => (-1, 0),
-            Dir::Right => (1,  0),
+            Dir::Up        => (0, -1),
+            Dir::Down      => (0,  1),
+            Dir::Left      => (-1, 0),
+            Dir::Right     => (1,  0),
+            Dir::UpLeft    => (-1, -1),
+            Dir::UpRight   => (1, -1),
+            Dir::DownLeft  => (-1, 1),
+            Dir::DownRight => (1,  1),
         }
     }
 
@@ -64,35 +192,135 @@ impl Dir {
     }
 }
 
+#[derive(Clone)]
 pub struct Game<const N: usize> {
     board: [[Option<Player>; N]; N],
     turn: Player,
     moves: Vec<u8>,
-    misplayed: Option<Player>,
+    // Set when the game ends before the board does
+    ended: Option<Ending>,
+    rules: Rules,
+    // Zobrist hash of the board and the player to move
+    hash: u64,
+    // Every move with the tiles it flipped, `None` for passes, for `undo`
+    history: Vec<Option<(Pos, Vec<Pos>)>>,
+}
+
+impl <const N: usize> Default for Game<N> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl <const N: usize> Game<N> {
     pub fn new() -> Self {
-        assert!(N % 2 == 0);
+        Self::with_rules(Rules::default())
+    }
+
+    pub fn with_rules(rules: Rules) -> Self {
+        assert!(N.is_multiple_of(2));
         let mut g = Self {
             board: [[None; N]; N],
             turn: Player::Black,
             moves: Vec::new(),
-            misplayed: None,
+            ended: None,
+            rules,
+            hash: 0,
+            history: Vec::new(),
         };
         g.board[N / 2 - 1][N / 2 - 1] = Some(Player::White);
         g.board[N / 2][N / 2 - 1] = Some(Player::Black);
         g.board[N / 2 - 1][N / 2] = Some(Player::Black);
         g.board[N / 2][N / 2] = Some(Player::White);
+        g.hash = g.full_hash(|pos| pos);
         g
     }
 
+    /// Sets up a position in the format of `to_position_str`. The game has no
+    /// move history, and the player to move may have to pass first.
+    pub fn from_position_str(position: &str) -> Result<Self, Box<dyn Error>> {
+        let mut fields = position.split_whitespace();
+        let (size, spaces) = fields.next().and_then(|board| board.split_once(':'))
+            .ok_or("expected <size>:<spaces>")?;
+        let size: usize = size.parse().map_err(|err| format!("invalid size {:?}: {}", size, err))?;
+        if size != N {
+            return Err(format!("position is for a {}x{} board, not {}x{}", size, size, N, N).into());
+        }
+        let spaces: Vec<char> = spaces.chars().filter(|&c| c != '/').collect();
+        if spaces.len() != N * N {
+            return Err(format!("expected {} spaces, got {}", N * N, spaces.len()).into());
+        }
+        let turn = match fields.next() {
+            Some("W") | Some("w") => Player::White,
+            Some("B") | Some("b") => Player::Black,
+            other => return Err(format!("expected W or B to move, got {:?}", other).into()),
+        };
+        let rules = match fields.next() {
+            None | Some("orthogonal") => Rules::Orthogonal,
+            Some("standard") => Rules::Standard,
+            Some(rules) => return Err(format!("unknown rules {:?}", rules).into()),
+        };
+        if let Some(extra) = fields.next() {
+            return Err(format!("unexpected {:?} after the rules", extra).into());
+        }
+
+        let mut game = Self::with_rules(rules);
+        game.turn = turn;
+        for (i, c) in spaces.into_iter().enumerate() {
+            game.board[i % N][i / N] = match c {
+                '.' => None,
+                'W' | 'w' => Some(Player::White),
+                'B' | 'b' => Some(Player::Black),
+                _ => return Err(format!("invalid space {:?}, expected '.', 'W' or 'B'", c).into()),
+            };
+        }
+        game.hash = game.full_hash(|pos| pos);
+        Ok(game)
+    }
+
+    /// The board size, a colon and one character per space, row by row from
+    /// the top left corner (`.` empty, `W` white, `B` black), then the player
+    /// to move and, unless they are orthogonal, the rules. Rows may be
+    /// separated by `/` when read. The initial 8x8 position is
+    /// `8:...........................WB......BW........................... B`
+    pub fn to_position_str(&self) -> String {
+        let spaces: String = (0..N).flat_map(|y| (0..N).map(move |x| Pos(x, y)))
+            .map(|pos| match self.space(pos) {
+                None => '.',
+                Some(Player::White) => 'W',
+                Some(Player::Black) => 'B',
+            })
+            .collect();
+        let turn = if self.turn == Player::White { "W" } else { "B" };
+        match self.rules {
+            Rules::Orthogonal => format!("{}:{} {}", N, spaces, turn),
+            Rules::Standard => format!("{}:{} {} standard", N, spaces, turn),
+        }
+    }
+
     pub fn current_player(&self) -> Player {
         self.turn
     }
 
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Number of tiles of `player`'s colour on the board.
+    pub fn count(&self, player: Player) -> usize {
+        self.board.iter().flatten().filter(|&&space| space == Some(player)).count()
+    }
+
+    /// The player whose tile is at `pos`, if any. `pos` must be on the board.
+    pub fn tile(&self, pos: Pos) -> Option<Player> {
+        self.space(pos)
+    }
+
     pub fn print(&self) {
+        let columns: String = (0..N).map(|x| (b'a' + x as u8) as char).collect();
+        println!("   {}", columns);
         for i in 0..N {
+            print!("{:>2} ", i + 1);
             for j in 0..N {
                 let pos = Pos(j,i);
                 match self.space(pos) {
@@ -105,28 +333,125 @@ impl <const N: usize> Game<N> {
                     }
                 }
             }
-            println!("");
+            println!();
         }
     }
 
     pub fn play(&mut self, pos: Pos) {
-        assert!(self.legal_moves(self.turn).contains(&pos));
         assert!(self.is_space(pos));
         assert!(self.board[pos.0][pos.1].is_none());
-        for flip_pos in self.flipped_if_placed(pos, self.turn) {
+        let flipped = self.flipped_if_placed(pos, self.turn);
+        assert!(!flipped.is_empty(), "illegal move {}", pos);
+        for &flip_pos in &flipped {
             self.set(flip_pos, self.turn);
         }
         self.set(pos, self.turn);
-        self.turn = self.turn.flip();
+        self.pass_turn();
         self.moves.push(pos.to_offset(N));
+        self.history.push(Some((pos, flipped)));
+    }
+
+    /// Takes back the last move or pass, and the end of the game if it ended
+    /// by misplay, resignation or agreement. Returns false if there is nothing
+    /// to take back, e.g. in a game set up from a position string.
+    pub fn undo(&mut self) -> bool {
+        let Some(last) = self.history.pop() else {
+            return false;
+        };
+        self.ended = None;
+        self.pass_turn();
+        if let Some((pos, flipped)) = last {
+            let opponent = self.turn.flip();
+            for flip_pos in flipped {
+                self.set(flip_pos, opponent);
+            }
+            self.hash ^= zobrist_key(pos, self.turn);
+            self.board[pos.0][pos.1] = None;
+            self.moves.pop();
+        }
+        true
+    }
+
+    /// Zobrist hash of the position, the board and the player to move. Kept
+    /// up to date by `play`, `skip` and `undo`.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The same for all positions that are equal up to rotating and
+    /// mirroring the board, which all play out the same way.
+    pub fn canonical_hash(&self) -> u64 {
+        let n = N - 1;
+        let symmetries: [Symmetry; 8] = [
+            |_, x, y| (x, y),
+            |n, x, y| (n - y, x),
+            |n, x, y| (n - x, n - y),
+            |n, x, y| (y, n - x),
+            |n, x, y| (n - x, y),
+            |n, x, y| (x, n - y),
+            |_, x, y| (y, x),
+            |n, x, y| (n - y, n - x),
+        ];
+        symmetries.iter()
+            .map(|symmetry| self.full_hash(|pos| {
+                let (x, y) = symmetry(n, pos.0, pos.1);
+                Pos(x, y)
+            }))
+            .min()
+            .expect("there are symmetries")
+    }
+
+    // Hash of the board with every tile moved by `transform`
+    fn full_hash(&self, transform: impl Fn(Pos) -> Pos) -> u64 {
+        let tiles = self.positions().into_iter()
+            .filter_map(|pos| self.space(pos).map(|player| zobrist_key(transform(pos), player)))
+            .fold(0, |hash, key| hash ^ key);
+        if self.turn == Player::Black {
+            tiles ^ black_to_move_key()
+        } else {
+            tiles
+        }
+    }
+
+    fn pass_turn(&mut self) {
+        self.turn = self.turn.flip();
+        self.hash ^= black_to_move_key();
     }
 
     pub fn misplay(&mut self, player: Player) {
-        self.misplayed = Some(player);
+        self.ended = Some(Ending::Misplay(player));
     }
 
     pub fn is_misplay(&self) -> bool {
-        self.misplayed.is_some()
+        self.misplayed_by().is_some()
+    }
+
+    pub fn misplayed_by(&self) -> Option<Player> {
+        match self.ended {
+            Some(Ending::Misplay(player)) => Some(player),
+            _ => None,
+        }
+    }
+
+    /// Ends the game with a loss for `player`.
+    pub fn resign(&mut self, player: Player) {
+        self.ended = Some(Ending::Resignation(player));
+    }
+
+    /// Ends the game in a draw.
+    pub fn agree_draw(&mut self) {
+        self.ended = Some(Ending::DrawAgreement);
+    }
+
+    /// How the game ended, `None` while it's still going.
+    pub fn ending(&self) -> Option<Ending> {
+        if self.ended.is_some() {
+            self.ended
+        } else if self.game_over() {
+            Some(Ending::Completed)
+        } else {
+            None
+        }
     }
 
     pub fn move_list(&self) -> &Vec<u8> {
@@ -147,19 +472,22 @@ impl <const N: usize> Game<N> {
 
     pub fn skip(&mut self) {
         assert!(self.legal_moves(self.turn).is_empty());
-        self.turn = self.turn.flip();
+        self.pass_turn();
+        self.history.push(None);
     }
 
     pub fn game_over(&self) -> bool {
-        self.misplayed.is_some() ||
+        self.ended.is_some() ||
             (self.legal_moves(self.turn).is_empty() &&
             self.legal_moves(self.turn.flip()).is_empty())
     }
 
     pub fn winner(&self) -> Option<Player> {
         assert!(self.game_over());
-        if self.misplayed.is_some() {
-            return self.misplayed.map(|p| p.flip());
+        match self.ended {
+            Some(Ending::Misplay(player)) | Some(Ending::Resignation(player)) => return Some(player.flip()),
+            Some(Ending::DrawAgreement) => return None,
+            Some(Ending::Completed) | None => {},
         }
 
         let non_empty_spaces = self.positions().into_iter().filter_map(|pos| self.space(pos));
@@ -184,27 +512,49 @@ impl <const N: usize> Game<N> {
             .collect()
     }
 
+    /// Tiles that would flip if the player to move placed a tile at `pos`,
+    /// none if the move is illegal.
+    pub fn flips(&self, pos: Pos) -> Vec<Pos> {
+        if !self.is_space(pos) {
+            return Vec::new();
+        }
+        self.flipped_if_placed(pos, self.turn)
+    }
+
     fn is_legal_move(&self, pos: Pos, player: Player) -> bool {
-        !self.flipped_if_placed(pos, player).is_empty()
+        self.space(pos).is_none() &&
+            self.rules.directions().iter().any(|&dir| self.flanks(pos, dir, player))
+    }
+
+    // Whether placing a tile at `pos` would flip any tiles in `dir`, without
+    // collecting them
+    fn flanks(&self, pos: Pos, dir: Dir, player: Player) -> bool {
+        let mut next = dir.add_to_pos(pos);
+        let mut seen_opponent = false;
+        while self.is_space(next) {
+            match self.space(next) {
+                Some(p) if p == player => return seen_opponent,
+                Some(_) => seen_opponent = true,
+                None => return false,
+            }
+            next = dir.add_to_pos(next);
+        }
+        false
     }
 
     fn flipped_if_placed(&self, pos: Pos, player: Player) -> Vec<Pos> {
-        [self.flipped_if_placed_dir(pos, Dir::Up, player),
-         self.flipped_if_placed_dir(pos, Dir::Down, player),
-         self.flipped_if_placed_dir(pos, Dir::Left, player),
-         self.flipped_if_placed_dir(pos, Dir::Right, player)].concat()
+        self.rules.directions().iter()
+            .flat_map(|&dir| self.flipped_if_placed_dir(pos, dir, player))
+            .collect()
     }
 
     fn flipped_if_placed_dir(&self, pos: Pos, dir: Dir, player: Player) -> Vec<Pos> {
         if self.space(pos).is_some() {
             return Vec::new()
         }
-        let positions_in_dir: Vec<Pos> = match dir {
-            Dir::Up => (0..pos.1).rev().map(|y| Pos(pos.0,y)).collect(),
-            Dir::Down => ((pos.1 + 1)..N).map(|y| Pos(pos.0,y)).collect(),
-            Dir::Left => (0..pos.0).rev().map(|x| Pos(x, pos.1)).collect(),
-            Dir::Right => ((pos.0 + 1)..N).map(|x| Pos(x, pos.1)).collect()
-        };
+        let positions_in_dir: Vec<Pos> = std::iter::successors(Some(dir.add_to_pos(pos)), |&p| Some(dir.add_to_pos(p)))
+            .take_while(|&p| self.is_space(p))
+            .collect();
         let res: Vec<Pos> = positions_in_dir.into_iter().take_while(|&p| match self.space(p) {
             Some(s) => s == player.flip(),
             _ => false
@@ -234,6 +584,20 @@ impl <const N: usize> Game<N> {
 
     fn set(&mut self, pos: Pos, player: Player) {
         assert!(self.is_space(pos));
+        if let Some(old) = self.board[pos.0][pos.1] {
+            self.hash ^= zobrist_key(pos, old);
+        }
+        self.hash ^= zobrist_key(pos, player);
         self.board[pos.0][pos.1] = Some(player);
     }
 }
+
+// Random key for a tile of `player`'s colour at `pos`, the same for every board size
+fn zobrist_key(pos: Pos, player: Player) -> u64 {
+    let index = ((pos.0 as u64) << 9 | (pos.1 as u64) << 1 | (player == Player::Black) as u64) + 1;
+    Rng::new(ZOBRIST_SEED.wrapping_add(index)).next_u64()
+}
+
+fn black_to_move_key() -> u64 {
+    Rng::new(ZOBRIST_SEED).next_u64()
+}
@@ -0,0 +1,221 @@
+use std::error::Error;
+use crate::game::Player;
+use crate::matchrunner::MatchRecord;
+use crate::tournament::{play_pairing, Pairing, PlayerFactory, Standings, TournamentResult, TournamentSettings};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Elimination {
+    /// Entrants are out after losing one match.
+    Single,
+    /// Entrants drop to a losers' bracket after their first lost match and
+    /// are out after their second. The winner of the losers' bracket has to
+    /// beat the winners' bracket champion twice in the grand final.
+    Double,
+}
+
+/// Knockout tournament where entrants play matches of a few games, with
+/// extra tiebreak games when a match is tied. Its threads play whole matches.
+#[derive(Debug, Clone)]
+pub struct Knockout {
+    settings: TournamentSettings,
+    elimination: Elimination,
+    games_per_match: usize,
+    tiebreaks: usize,
+}
+
+// One match between two entrants. `first` is the higher seed.
+#[derive(Debug, Clone)]
+struct Series {
+    round: usize,
+    first: usize,
+    second: usize,
+    first_game: usize,
+}
+
+#[derive(Debug)]
+struct SeriesResult {
+    winner: usize,
+    loser: usize,
+    games: Vec<(Pairing, MatchRecord)>,
+}
+
+impl Knockout {
+    /// The entrants are in seeding order, strongest first. Seeds are placed
+    /// so that the strongest entrants meet as late as possible, and entrants
+    /// without an opponent in the first round get a bye.
+    pub fn new(settings: TournamentSettings, elimination: Elimination) -> Self {
+        Self { settings, elimination, games_per_match: 2, tiebreaks: 2 }
+    }
+
+    /// Games per match, alternating colours with the higher seed playing white first.
+    pub fn games_per_match(mut self, games: usize) -> Self {
+        self.games_per_match = games.max(1);
+        self
+    }
+
+    /// Pairs of tiebreak games, one with each colour, played while a match is
+    /// tied. If it's still tied after all of them the entrant with the better
+    /// disc difference goes through, then the higher seed.
+    pub fn tiebreaks(mut self, tiebreaks: usize) -> Self {
+        self.tiebreaks = tiebreaks;
+        self
+    }
+
+    pub fn settings(&self) -> &TournamentSettings {
+        &self.settings
+    }
+
+    // Games a single match can take at most, used to number games before they are played
+    fn max_games_per_match(&self) -> usize {
+        self.games_per_match + 2 * self.tiebreaks
+    }
+
+    /// Plays the bracket. The standings are ordered by how far entrants got,
+    /// and within the same round of elimination by score.
+    pub fn run<const N: usize>(&self, new_player: &PlayerFactory<N>) -> Result<TournamentResult, Box<dyn Error>> {
+        let mut games = Vec::new();
+        let mut byes = Vec::new();
+        // (entrant, round it was knocked out in)
+        let mut eliminated: Vec<(usize, usize)> = Vec::new();
+        let mut next_game = 0;
+        let mut round = 0;
+        let mut play_round = |round: usize, pairs: Vec<(usize, usize)>, games: &mut Vec<(Pairing, MatchRecord)>| -> Result<Vec<SeriesResult>, Box<dyn Error>> {
+            let series: Vec<Series> = pairs.into_iter().map(|(first, second)| {
+                let series = Series { round, first: first.min(second), second: first.max(second), first_game: next_game };
+                next_game += self.max_games_per_match();
+                series
+            }).collect();
+            let results = self.settings.executor().run(&series, |series| self.play_series(series, new_player))
+                .into_iter().collect::<Result<Vec<_>, _>>()?;
+            for result in &results {
+                games.extend(result.games.iter().cloned());
+            }
+            Ok(results)
+        };
+
+        // Winners' bracket, `None` marks a bye
+        let entrants = self.settings.entrants.len();
+        let mut winners: Vec<Option<usize>> = bracket_order(entrants).into_iter()
+            .map(|seed| if seed < entrants { Some(seed) } else { None })
+            .collect();
+        let mut losers: Vec<usize> = Vec::new();
+        let champion = loop {
+            let alive: Vec<usize> = winners.iter().flatten().copied().collect();
+            if alive.len() <= 1 && losers.is_empty() {
+                break alive.first().copied();
+            }
+            if alive.len() == 1 && losers.len() == 1 {
+                // Grand final, the losers' bracket winner has to win twice
+                let (champion, runner_up) = (alive[0], losers[0]);
+                let result = play_round(round, vec![(champion, runner_up)], &mut games)?.remove(0);
+                round += 1;
+                if result.winner == champion {
+                    eliminated.push((runner_up, round));
+                    break Some(champion);
+                }
+                let result = play_round(round, vec![(champion, runner_up)], &mut games)?.remove(0);
+                round += 1;
+                eliminated.push((result.loser, round));
+                break Some(result.winner);
+            }
+
+            let mut dropped = Vec::new();
+            if alive.len() > 1 {
+                let mut pairs = Vec::new();
+                for pair in winners.chunks(2) {
+                    match *pair {
+                        [Some(a), Some(b)] => pairs.push((a, b)),
+                        [Some(a), None] | [None, Some(a)] | [Some(a)] => byes.push((round, a)),
+                        _ => {},
+                    }
+                }
+                let results = play_round(round, pairs, &mut games)?;
+                // Winners keep their slot in the bracket
+                let mut results = results.into_iter();
+                winners = winners.chunks(2).map(|pair| match *pair {
+                    [Some(_), Some(_)] => {
+                        let result = results.next().expect("one result per match");
+                        dropped.push(result.loser);
+                        Some(result.winner)
+                    },
+                    [Some(a), None] | [None, Some(a)] | [Some(a)] => Some(a),
+                    _ => None,
+                }).collect();
+            }
+
+            if self.elimination == Elimination::Double && losers.len() > 1 {
+                let pairs: Vec<(usize, usize)> = losers.chunks(2).filter(|c| c.len() == 2).map(|c| (c[0], c[1])).collect();
+                if losers.len() % 2 == 1 {
+                    byes.push((round, *losers.last().unwrap()));
+                }
+                let results = play_round(round, pairs, &mut games)?;
+                let mut survivors: Vec<usize> = results.iter().map(|r| r.winner).collect();
+                if losers.len() % 2 == 1 {
+                    survivors.push(*losers.last().unwrap());
+                }
+                eliminated.extend(results.iter().map(|r| (r.loser, round)));
+                losers = survivors;
+            }
+            match self.elimination {
+                Elimination::Single => eliminated.extend(dropped.into_iter().map(|e| (e, round))),
+                Elimination::Double => losers.extend(dropped),
+            }
+            round += 1;
+        };
+
+        let bye_entrants: Vec<usize> = byes.iter().map(|&(_, e)| e).collect();
+        let mut standings = Standings::new(&self.settings.entrants, &games, &bye_entrants);
+        // Champion first, then by how late entrants were knocked out, keeping
+        // the score order of `Standings` within a round
+        let reached = |entrant: usize| if Some(entrant) == champion {
+            usize::MAX
+        } else {
+            eliminated.iter().find(|&&(e, _)| e == entrant).map_or(0, |&(_, round)| round)
+        };
+        standings.0.sort_by_key(|s| std::cmp::Reverse(reached(s.entrant)));
+        Ok(TournamentResult { games, byes, standings })
+    }
+
+    fn play_series<const N: usize>(&self, series: &Series, new_player: &PlayerFactory<N>) -> Result<SeriesResult, String> {
+        let mut games = Vec::new();
+        let mut score = 0.0; // of `first`, minus that of `second`
+        let mut discs = 0i64;
+        let mut played = 0;
+        while played < self.max_games_per_match() {
+            // Tiebreak games come in pairs, so a decided match stops after
+            // the regular games or after a whole pair
+            if played >= self.games_per_match && (played - self.games_per_match).is_multiple_of(2) && score != 0.0 {
+                break;
+            }
+            let (white, black) = if played % 2 == 0 { (series.first, series.second) } else { (series.second, series.first) };
+            let pairing = Pairing { game: series.first_game + played, round: series.round, white, black, opening: None };
+            let record = play_pairing(self.settings.match_config(&pairing), &pairing, new_player)?;
+            let first_colour = if white == series.first { Player::White } else { Player::Black };
+            score += match record.winner {
+                None => 0.0,
+                Some(winner) if winner == first_colour => 1.0,
+                Some(_) => -1.0,
+            };
+            let difference = record.white_count as i64 - record.black_count as i64;
+            discs += if first_colour == Player::White { difference } else { -difference };
+            games.push((pairing, record));
+            played += 1;
+        }
+        let first_wins = score > 0.0 || (score == 0.0 && discs >= 0);
+        let (winner, loser) = if first_wins { (series.first, series.second) } else { (series.second, series.first) };
+        Ok(SeriesResult { winner, loser, games })
+    }
+}
+
+// Seed positions in a bracket of the next power of two, such that seeds 0
+// and 1 can only meet in the final, 0 to 3 in the semi finals and so on.
+// Positions with a seed of `n` or more are byes.
+fn bracket_order(n: usize) -> Vec<usize> {
+    let size = n.next_power_of_two();
+    let mut order = vec![0];
+    while order.len() < size {
+        let len = order.len() * 2;
+        order = order.into_iter().flat_map(|seed| [seed, len - 1 - seed]).collect();
+    }
+    order
+}
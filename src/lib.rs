@@ -1,33 +1,149 @@
 #![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
 #[cfg(not(target_arch = "wasm32"))]
 mod wasmplayer;
+#[cfg(not(target_arch = "wasm32"))]
+mod profile;
 mod game;
+mod observer;
+mod rng;
+mod opening;
+#[cfg(not(target_arch = "wasm32"))]
+mod executor;
+mod rating;
+mod record;
+mod solver;
+mod referenceplayers;
+mod mctsplayer;
+mod replayplayer;
+mod engine;
+#[cfg(not(target_arch = "wasm32"))]
+mod textprotocol;
+#[cfg(not(target_arch = "wasm32"))]
+mod processplayer;
+#[cfg(not(target_arch = "wasm32"))]
+mod socketplayer;
+#[cfg(not(target_arch = "wasm32"))]
+mod matchrunner;
+#[cfg(not(target_arch = "wasm32"))]
+mod tournament;
+#[cfg(not(target_arch = "wasm32"))]
+mod swiss;
+#[cfg(not(target_arch = "wasm32"))]
+mod knockout;
+#[cfg(not(target_arch = "wasm32"))]
+mod sprt;
 #[cfg(not(target_arch = "wasm32"))]
 pub use crate::wasmplayer::WasmPlayer;
-pub use crate::game::{Game, Pos, Player, PlayerController};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::profile::{ProfileReport, MoveProfile, FunctionProfile};
+pub use crate::game::{Ending, Game, Pos, Player, PlayerController, Rules, Turn};
+pub use crate::observer::{GameObserver, SilentObserver, TerminalObserver};
+pub use crate::opening::{Opening, OpeningSuite};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::executor::{Executor, Progress};
+pub use crate::rating::{Elo, Glicko2};
+pub use crate::record::{GameRecord, RecordedMove};
+pub use crate::solver::{Solution, Solver};
+pub use crate::referenceplayers::{reference_player, AlphaBetaPlayer, GreedyPlayer, PositionalPlayer, RandomPlayer, REFERENCE_PLAYERS};
+pub use crate::mctsplayer::{MctsBudget, MctsPlayer};
+pub use crate::replayplayer::ReplayPlayer;
+pub use crate::engine::{Engine, MoveEvaluation, ENGINES};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::textprotocol::{serve_player, PROTOCOL_VERSION};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::processplayer::ProcessPlayer;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::socketplayer::SocketPlayer;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::matchrunner::{DrawOffer, MatchConfig, MatchRecord, MatchRunner, Misplay, MisplayPolicy, TimeControl};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::tournament::{Pairing, PlayerFactory, Standing, Standings, Tournament, TournamentResult, TournamentSettings};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::swiss::Swiss;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::knockout::{Elimination, Knockout};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::sprt::{elo_difference, Sprt, SprtDecision, SprtReport};
+use std::error::Error;
+use std::fmt;
 
+/// Plays a game from the initial position with the default `MatchConfig`.
 #[cfg(not(target_arch = "wasm32"))]
 pub fn play_game<const N: usize>(white_player: &mut dyn PlayerController<N>, black_player: &mut dyn PlayerController<N>) -> Game<N> {
-    let mut game = Game::<N>::new();
-    while !game.game_over() {
-        let legal_moves = game.legal_moves(game.current_player());
-        if legal_moves.is_empty() {
-            game.skip();
-            // Game is over if neither can play
-            assert!(!game.legal_moves(game.current_player()).is_empty());
+    MatchRunner::<N>::new(MatchConfig::default())
+        .expect("default config is valid")
+        .run(white_player, black_player)
+        .to_game()
+}
+
+/// A replayed game did not follow its recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMismatch {
+    /// Index of the first move that differs, or the length of the shorter
+    /// move list if one is a prefix of the other.
+    pub index: usize,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+}
+
+impl fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "replay diverged at move {}: expected {:?}, got {:?}", self.index, self.expected, self.actual)
+    }
+}
+
+impl Error for ReplayMismatch {}
+
+/// Plays a new match between the players and checks that it produces exactly
+/// the recorded move list. Players must be fresh, e.g. newly instantiated
+/// bots, and `runner` configured the same way as for the recorded match.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn verify_replay<const N: usize>(runner: &mut MatchRunner<N>, white_player: &mut dyn PlayerController<N>, black_player: &mut dyn PlayerController<N>, moves: &[u8]) -> Result<MatchRecord, ReplayMismatch> {
+    let record = runner.run(white_player, black_player);
+    let replayed = &record.moves;
+    match moves.iter().zip(replayed.iter()).position(|(a, b)| a != b) {
+        Some(index) => Err(ReplayMismatch { index, expected: Some(moves[index]), actual: Some(replayed[index]) }),
+        None if moves.len() != replayed.len() => {
+            let index = moves.len().min(replayed.len());
+            Err(ReplayMismatch { index, expected: moves.get(index).copied(), actual: replayed.get(index).copied() })
+        },
+        None => Ok(record),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: &[u8] = include_bytes!("../examples/rust/hello.wasm");
+    const SEED: u64 = 7;
+
+    // hello.wasm as white against a seeded random player, with fresh players every time
+    fn play(moves: Option<&[u8]>) -> Result<MatchRecord, ReplayMismatch> {
+        let mut runner = MatchRunner::<8>::new(MatchConfig::default().seed(SEED)).unwrap();
+        let mut white = WasmPlayer::<8>::new(HELLO).unwrap();
+        let mut black = RandomPlayer::new(SEED);
+        match moves {
+            None => Ok(runner.run(&mut white, &mut black)),
+            Some(moves) => verify_replay(&mut runner, &mut white, &mut black, moves),
         }
-        let play = if game.current_player() == Player::White {
-            white_player.make_play(&game).map_err(|_| Player::White)
-        } else {
-            black_player.make_play(&game).map_err(|_| Player::Black)
-        };
-        match play {
-            Ok(pos) => {
-                println!("Answer: {:?}", pos);
-                game.play(pos);
-            },
-            Err(player) => game.misplay(player)
-        };
     }
-    game
+
+    #[test]
+    fn replay_matches_the_recorded_game() {
+        let recorded = play(None).unwrap();
+        assert_eq!(recorded.ending, Ending::Completed);
+        let replayed = play(Some(&recorded.moves)).unwrap();
+        assert_eq!(replayed.moves, recorded.moves);
+    }
+
+    #[test]
+    fn replay_finds_a_tampered_move() {
+        let mut moves = play(None).unwrap().moves;
+        let index = moves.len() / 2;
+        let original = moves[index];
+        moves[index] = (original + 1) % 64;
+        assert_eq!(play(Some(&moves)).unwrap_err(), ReplayMismatch { index, expected: Some(moves[index]), actual: Some(original) });
+    }
 }
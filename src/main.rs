@@ -1,50 +1,579 @@
 #![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
 mod terminalplayer;
-mod wasmplayer;
-mod game;
-extern crate wasmtime;
+mod tuiplayer;
 use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
 use wasmthello::WasmPlayer;
-use crate::terminalplayer::TerminalPlayer;
+use wasmthello::{elo_difference, reference_player, Engine, Game, GameObserver, Player, PlayerController, Rules, Turn};
+use wasmthello::{GameRecord, MatchConfig, MatchRecord, MatchRunner, Opening, OpeningSuite, ProcessPlayer, RandomPlayer, ReplayPlayer, Sprt, TerminalObserver, Tournament, TournamentSettings};
+use crate::terminalplayer::{print_hints, TerminalPlayer};
+use crate::tuiplayer::TuiPlayer;
 
-use std::io;
-use std::io::Read;
-use std::io::BufReader;
-use std::fs::File;
+const USAGE: &str = "\
+usage: wasmthello <command> [options]
+
+commands:
+  play [--white <player>] [--black <player>] [--games <n>] [--swap] [--save <file>]
+      play a game, by default between two humans at the same terminal. With
+      --games, play a series and keep a running score, and with --swap, swap
+      colours every game. --save numbers the files of a series
+  match <player> <player> [--games <n>] [--threads <n>]
+      play a series of games from random openings, swapping colours. Every
+      opening is played with both colours, so an odd number of games is
+      rounded up
+  sprt <candidate> <baseline> [--elo0 <n>] [--elo1 <n>] [--alpha <p>] [--beta <p>]
+       [--max-games <n>] [--threads <n>]
+      play pairs of games from random openings until the candidate is shown
+      to be at least elo1 (5 by default) or at most elo0 (0 by default)
+      stronger than the baseline, wrongly accepting with chance alpha and
+      wrongly rejecting with chance beta (both 0.05 by default)
+  validate <wasm file>
+      check that a bot exports the right functions and plays legal moves
+  replay <record file>
+      step through a recorded game
+  reproduce <record file> <white|black> <player>
+      play one side of a recorded game live and the other from the record
+  analyse <position> [--bot <player>] [--engine <engine>]
+      show a position, the engine's score of every legal move and what a bot
+      would play in it
+  analyse <record file> [--engine <engine>]
+      annotate a game with the engine's score after every move, and the
+      better moves where the score swung against the player
+
+options for play, match, sprt and validate:
+  --size <8|12|16>               board size, 8 by default
+  --rules <orthogonal|standard>  which lines flip, orthogonal by default
+  --seed <n>                     seed for openings and random misplay moves, 0 by default
+
+players:
+  human[:<engine>]       moves typed in the terminal, 'hint' scores them with the engine
+  tui                    a human on a full screen board, moving with the arrow keys
+  <file>.wasm, <file>.wat  a wasm bot
+  process:<command>      a bot speaking the line protocol over stdio
+  random[:<seed>], greedy, positional, alphabeta[:<depth>], mcts[:<playouts>]
+                         built-in reference players
+
+engines:
+  positional, alphabeta[:<depth>], solver
+                         score moves by square weights and mobility, searching
+                         with alpha-beta to depth 4 by default, or solving the
+                         endgame exactly once 14 empty squares are left";
+
+// Engine for hints and analysis when none is given
+const DEFAULT_ENGINE: &str = "alphabeta";
+
+// Plies of the random openings `match` plays from
+const MATCH_OPENING_PLIES: usize = 4;
+
+// Calls `$function::<N>(...)` with the board size as `N`
+macro_rules! with_board_size {
+    ($size:expr, $function:ident($($arg:expr),*)) => {
+        match $size {
+            8 => $function::<8>($($arg),*),
+            12 => $function::<12>($($arg),*),
+            16 => $function::<16>($($arg),*),
+            size => Err(format!("unsupported board size {}", size).into()),
+        }
+    };
+}
+
+// Options that take no value
+const FLAGS: [&str; 1] = ["swap"];
 
-fn pick_file() -> Option<File> {
-    let mut input = String::new();
-    println!("enter path to wasm (q to abort):");
-    io::stdin().read_line(&mut input).ok()?;
-    let ans = input.trim_end_matches('\n');
-    if ans == "q" {
-        return None;
+// Positional arguments, `--name value` options and `--name` flags of a command
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut parsed = Args { positional: Vec::new(), options: Vec::new() };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if FLAGS.contains(&name) => parsed.options.push((name.to_string(), String::new())),
+                Some(name) => {
+                    let value = args.next().ok_or_else(|| format!("--{} needs a value", name))?;
+                    parsed.options.push((name.to_string(), value.clone()));
+                },
+                None => parsed.positional.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn option(&mut self, name: &str) -> Option<String> {
+        let index = self.options.iter().position(|(option, _)| option == name)?;
+        Some(self.options.remove(index).1)
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        self.option(name).is_some()
+    }
+
+    fn parsed_option<T: FromStr>(&mut self, name: &str, default: T) -> Result<T, Box<dyn Error>> where T::Err: Display {
+        match self.option(name) {
+            Some(value) => value.parse().map_err(|err| format!("invalid --{} {:?}: {}", name, value, err).into()),
+            None => Ok(default),
+        }
     }
 
-    match File::open(ans) {
-        Ok(file) => Some(file),
-        Err(err) => {
-            println!("could not open file '{}': {}", ans, err);
-            pick_file()
+    // The positional arguments, if there are `count` of them and no unknown options
+    fn finish(self, count: usize) -> Result<Vec<String>, Box<dyn Error>> {
+        if let Some((name, _)) = self.options.first() {
+            return Err(format!("unknown option --{}\n\n{}", name, USAGE).into());
+        }
+        if self.positional.len() != count {
+            return Err(format!("expected {} arguments, got {}\n\n{}", count, self.positional.len(), USAGE).into());
         }
+        Ok(self.positional)
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    const SIZE: usize = 8;
+// Options of the commands that play games
+struct BoardOptions {
+    size: usize,
+    rules: Rules,
+    seed: u64,
+}
 
-    let f = pick_file();
-    if f.is_none() {
-        return Ok(());
+impl BoardOptions {
+    fn parse(args: &mut Args) -> Result<Self, Box<dyn Error>> {
+        let rules = match args.option("rules").as_deref() {
+            None | Some("orthogonal") => Rules::Orthogonal,
+            Some("standard") => Rules::Standard,
+            Some(rules) => return Err(format!("unknown rules {:?}, expected orthogonal or standard", rules).into()),
+        };
+        Ok(Self { size: args.parsed_option("size", 8)?, rules, seed: args.parsed_option("seed", 0)? })
+    }
+
+    fn match_config(&self) -> MatchConfig {
+        MatchConfig::default().rules(self.rules).seed(self.seed)
+    }
+}
+
+fn player_from_name<const N: usize>(name: &str) -> Result<Box<dyn PlayerController<N>>, Box<dyn Error>> where [(); N*N*2]: Sized {
+    if name == "human" || name.starts_with("human:") {
+        let engine = Engine::from_name(name.strip_prefix("human:").unwrap_or(DEFAULT_ENGINE))?;
+        return Ok(Box::new(TerminalPlayer::<N>::new(engine)));
+    }
+    if name == "tui" {
+        return Ok(Box::new(TuiPlayer::<N>::new()));
+    }
+    if let Some(command) = name.strip_prefix("process:") {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("process: needs a command")?;
+        return Ok(Box::new(ProcessPlayer::spawn(Command::new(program).args(words))?));
+    }
+    if name.ends_with(".wasm") || name.ends_with(".wat") {
+        let wasm = fs::read(name).map_err(|err| format!("could not open file '{}': {}", name, err))?;
+        return Ok(Box::new(WasmPlayer::<N>::new(&wasm)?));
+    }
+    reference_player::<N>(name).map_err(|err| format!("{}, human, tui, process:<command> or a .wasm file", err).into())
+}
+
+// Plays `games` games between the players given as `--white` and `--black`,
+// swapping their colours every other game if `swap` is set
+fn play<const N: usize>(board: &BoardOptions, players: [&str; 2], games: usize, swap: bool, save: Option<&str>) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    // Tells the players apart in the score when e.g. both are `human`
+    let names = if players[0] == players[1] {
+        [format!("{} (1)", players[0]), format!("{} (2)", players[1])]
+    } else {
+        players.map(str::to_string)
+    };
+    let mut points = [0.0; 2];
+    for number in 1..=games {
+        let (white, black) = if swap && number % 2 == 0 { (1, 0) } else { (0, 1) };
+        if games > 1 {
+            println!("game {} of {}: {} plays white, {} plays black", number, games, names[white], names[black]);
+        }
+        let record = play_game::<N>(board, players[white], players[black])?;
+        match record.winner {
+            Some(Player::White) => points[white] += 1.0,
+            Some(Player::Black) => points[black] += 1.0,
+            None => {
+                points[0] += 0.5;
+                points[1] += 0.5;
+            },
+        }
+        if let Some(path) = save {
+            let path = if games > 1 { numbered_path(path, number) } else { path.to_string() };
+            fs::write(&path, GameRecord::from_match_record::<N>(&record, players[white], players[black]).to_string())?;
+            println!("saved the game to {}", path);
+        }
+        if games > 1 {
+            println!("score after {} of {} games: {} {} - {} {}", number, games, names[0], points[0], points[1], names[1]);
+        }
+    }
+    Ok(())
+}
+
+fn play_game<const N: usize>(board: &BoardOptions, white: &str, black: &str) -> Result<MatchRecord, Box<dyn Error>> where [(); N*N*2]: Sized {
+    let mut white_player = player_from_name::<N>(white)?;
+    let mut black_player = player_from_name::<N>(black)?;
+    // Humans may take back moves, and the full screen board replaces the printed one
+    let humans = [white, black].iter().any(|&name| name == "human" || name.starts_with("human:") || name == "tui");
+    let tui = white == "tui" || black == "tui";
+    let mut runner = MatchRunner::<N>::new(board.match_config().allow_undo(humans))?;
+    if !tui {
+        runner = runner.observer(Box::new(TerminalObserver));
+    }
+    let record = runner.run(white_player.as_mut(), black_player.as_mut());
+    if tui {
+        // Leave the full screen before printing the result
+        drop(white_player);
+        drop(black_player);
+        TerminalObserver.on_game_over(&record.to_game::<N>());
+    }
+    Ok(record)
+}
+
+// `path` with `-<number>` added before its extension, for saving a series of games
+fn numbered_path(path: &str, number: usize) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => format!("{}-{}.{}", stem, number, extension),
+        _ => format!("{}-{}", path, number),
+    }
+}
+
+fn play_match<const N: usize>(board: &BoardOptions, first: &str, second: &str, games: usize, threads: usize) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    // Fail on bad players before starting any games
+    player_from_name::<N>(first)?;
+    player_from_name::<N>(second)?;
+    let names = [first, second];
+    let settings = TournamentSettings::new(names.map(str::to_string).to_vec())
+        .config(board.match_config())
+        .threads(threads);
+    // Every opening is played twice in a row, with `first` as white and then
+    // as black. There may be fewer distinct openings than pairs of games.
+    let pairs = games.div_ceil(2);
+    let openings = OpeningSuite::random::<N>(pairs, MATCH_OPENING_PLIES, board.rules, board.seed).openings().to_vec();
+    let tournament = match openings.len() {
+        0 => Tournament::round_robin(settings).repeats(games),
+        _ => Tournament::round_robin(settings).openings(OpeningSuite::new(openings.into_iter().cycle().take(pairs).collect())),
+    };
+    let result = tournament.run::<N>(&|entrant| player_from_name::<N>(names[entrant]))?;
+
+    for (pairing, record) in &result.games {
+        let result = match record.winner {
+            Some(Player::White) => "1-0",
+            Some(Player::Black) => "0-1",
+            None => "1/2-1/2",
+        };
+        println!("game {}: {} (white) vs {} (black): {} {}-{}, {}", pairing.game + 1, names[pairing.white], names[pairing.black],
+                 result, record.white_count, record.black_count, record.ending);
+        for misplay in &record.misplays {
+            println!("    {:?} misplayed at move {}: {}", misplay.player, misplay.move_number + 1, misplay.reason);
+        }
+    }
+    let standing = result.standings.0.iter().find(|standing| standing.entrant == 0).expect("every entrant has a standing");
+    let score = standing.points / standing.played as f64;
+    let elo = elo_difference(score);
+    println!("{} against {}: {} wins, {} draws, {} losses, score {:.1}%, Elo difference {:+.0}",
+             first, second, standing.wins, standing.draws, standing.losses, 100.0 * score, elo);
+    Ok(())
+}
+
+// Plays `test`, with the candidate and baseline named in its settings, from
+// random openings
+fn sprt<const N: usize>(board: &BoardOptions, test: Sprt, max_games: usize) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    let names = test.settings().entrants().to_vec();
+    // Fail on bad players before starting any games
+    for name in &names {
+        player_from_name::<N>(name)?;
+    }
+    let suite = OpeningSuite::random::<N>(max_games.div_ceil(2), MATCH_OPENING_PLIES, board.rules, board.seed);
+    let report = test.openings(suite).run::<N>(&|entrant| player_from_name::<N>(&names[entrant]))?;
+    println!("{} against {}", names[0], names[1]);
+    print!("{}", report);
+    Ok(())
+}
+
+// Instantiating the bot checks its exports, and games against a random
+// player from both sides check that it answers with legal moves
+fn validate<const N: usize>(board: &BoardOptions, path: &str) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    let wasm = fs::read(path).map_err(|err| format!("could not open file '{}': {}", path, err))?;
+    WasmPlayer::<N>::new(&wasm).map_err(|err| format!("invalid bot: {}", err))?;
+    println!("exports are valid");
+    let mut misplays = 0;
+    for (i, colour) in [Player::White, Player::Black].into_iter().enumerate() {
+        let mut bot = WasmPlayer::<N>::new(&wasm)?;
+        let mut opponent = RandomPlayer::new(board.seed.wrapping_add(i as u64));
+        let runner = MatchRunner::<N>::new(board.match_config());
+        let record = match colour {
+            Player::White => runner?.run(&mut bot, &mut opponent),
+            Player::Black => runner?.run(&mut opponent, &mut bot),
+        };
+        println!("played {} moves as {:?}: {}", record.moves.len(), colour, record.ending);
+        for misplay in record.misplays.iter().filter(|misplay| misplay.player == colour) {
+            println!("    misplayed at move {}: {}", misplay.move_number + 1, misplay.reason);
+            misplays += 1;
+        }
+    }
+    if misplays > 0 {
+        return Err(format!("{} misplays", misplays).into());
+    }
+    println!("{} is a valid bot for {}x{} boards", path, N, N);
+    Ok(())
+}
+
+fn replay<const N: usize>(record: &GameRecord) -> Result<(), Box<dyn Error>> {
+    let mut game = Game::<N>::with_rules(record.rules()?);
+    for (name, value) in &record.tags {
+        println!("{}: {}", name, value);
+    }
+    if let Some(comment) = &record.comment {
+        println!("{{{}}}", comment);
+    }
+    let mut next = 0;
+    loop {
+        game.print();
+        println!("white {} black {}", game.count(Player::White), game.count(Player::Black));
+        if next == record.moves.len() {
+            println!("end of the game");
+        }
+        println!("enter for the next move, b to go back, q to quit");
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+        match input.trim() {
+            "q" => return Ok(()),
+            "b" if next > 0 => {
+                game.undo();
+                next -= 1;
+            },
+            "b" => println!("at the start of the game"),
+            _ if next == record.moves.len() => {},
+            _ => {
+                let recorded = &record.moves[next];
+                let player = game.current_player();
+                match recorded.pos {
+                    Some(pos) if !game.flips(pos).is_empty() => game.play(pos),
+                    Some(pos) => return Err(format!("move {}: illegal move {}", next + 1, pos).into()),
+                    None if game.legal_moves(player).is_empty() => game.skip(),
+                    None => return Err(format!("move {}: pass while there are legal moves", next + 1).into()),
+                }
+                next += 1;
+                let notation = recorded.pos.map_or("pass".to_string(), |pos| pos.to_string());
+                println!("{}. {:?}: {}", next, player, notation);
+                if let Some(comment) = &recorded.comment {
+                    println!("{{{}}}", comment);
+                }
+            },
+        }
+    }
+}
+
+// Plays `live`'s side of a recorded game with the player `name`, and the
+// other side from the record, to reproduce the game and find where it
+// stops following the record
+fn reproduce<const N: usize>(record: &GameRecord, live: Player, name: &str) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    let moves = record.to_game::<N>()?.move_list().clone();
+    let mut config = MatchConfig::default().rules(record.rules()?);
+    // Openings of server games are identified by their moves, e.g. 26-34-42-33
+    let opening: Option<Vec<u8>> = record.tag("Opening")
+        .and_then(|id| id.split('-').map(|offset| offset.parse().ok()).collect());
+    if let Some(opening) = opening.filter(|opening| moves.starts_with(opening)) {
+        config = config.opening(Opening::new(opening));
+    }
+    let mut live_player = player_from_name::<N>(name)?;
+    let mut replay_player = ReplayPlayer::new(moves.clone());
+    let (white, black): (&mut dyn PlayerController<N>, &mut dyn PlayerController<N>) = match live {
+        Player::White => (live_player.as_mut(), &mut replay_player),
+        Player::Black => (&mut replay_player, live_player.as_mut()),
+    };
+    let match_record = MatchRunner::<N>::new(config)?
+        .observer(Box::new(TerminalObserver))
+        .run(white, black);
+    for misplay in &match_record.misplays {
+        println!("{:?} misplayed at move {}: {}", misplay.player, misplay.move_number + 1, misplay.reason);
+    }
+    if match_record.moves == moves {
+        println!("reproduced all {} moves of the record", moves.len());
+    } else {
+        println!("the game did not follow the record");
     }
+    Ok(())
+}
 
-    let mut reader = BufReader::new(f.unwrap());
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
+// Prints a position given in the format of `Game::to_position_str`, and
+// what a bot would play in it.
+fn analyse_position<const N: usize>(position: &str, bot: Option<&str>, engine: Engine) -> Result<(), Box<dyn Error>> where [(); N*N*2]: Sized {
+    let game = Game::<N>::from_position_str(position)?;
+    game.print();
+    println!("{:?} to move, white {} black {}", game.current_player(), game.count(Player::White), game.count(Player::Black));
+    let legal_moves: Vec<String> = game.legal_moves(game.current_player()).iter().map(|pos| pos.to_string()).collect();
+    if legal_moves.is_empty() {
+        println!("no legal moves");
+        return Ok(());
+    }
+    println!("legal moves: {}", legal_moves.join(" "));
+    print_hints(&game, engine);
+    if let Some(bot) = bot {
+        let mut player = player_from_name::<N>(bot)?;
+        match player.take_turn(&game)? {
+            Turn::Move(pos) => println!("{} plays {}", bot, pos),
+            turn => println!("{} answers {:?}", bot, turn),
+        }
+    }
+    Ok(())
+}
 
-    let mut white_player = WasmPlayer::<SIZE>::new(&buffer)?;
-    let mut black_player = TerminalPlayer::<SIZE> {};
-    let game = wasmthello::play_game::<SIZE>(&mut white_player, &mut black_player);
-    println!("Winner: {:?}", game.winner());
+// Scores every move of the game for Black, and lists where the player to move
+// lost the most against the best move
+fn analyse_game<const N: usize>(record: &GameRecord, engine: Engine) -> Result<(), Box<dyn Error>> {
+    let mut game = Game::<N>::with_rules(record.rules()?);
+    for (name, value) in &record.tags {
+        println!("{}: {}", name, value);
+    }
+    println!("score for black after each move, and the best move when the played one was worse");
+    // How much worse than the best move each move was, for the player making it
+    let mut swings = Vec::new();
+    for (number, recorded) in record.moves.iter().enumerate() {
+        let player = game.current_player();
+        let Some(pos) = recorded.pos else {
+            if !game.legal_moves(player).is_empty() {
+                return Err(format!("move {}: pass while there are legal moves", number + 1).into());
+            }
+            game.skip();
+            println!("{:>3}. {:?} passes", number + 1, player);
+            continue;
+        };
+        let evaluations = engine.evaluate_moves(&game);
+        let played = evaluations.iter().find(|evaluation| evaluation.pos == pos)
+            .ok_or_else(|| format!("move {}: illegal move {}", number + 1, pos))?;
+        let best = evaluations[0];
+        let for_black = if player == Player::Black { played.score } else { -played.score };
+        let swing = best.score - played.score;
+        if swing > 0 {
+            println!("{:>3}. {:<5} {:<4} {:>+7}  best {}, {} better", number + 1, format!("{:?}", player), pos.to_string(), for_black, best.pos, swing);
+            swings.push((swing, number, player, pos, best.pos));
+        } else {
+            println!("{:>3}. {:<5} {:<4} {:>+7}", number + 1, format!("{:?}", player), pos.to_string(), for_black);
+        }
+        game.play(pos);
+    }
+    // Ends the game the way the record says, e.g. by resignation
+    let game = record.to_game::<N>()?;
+    let result = match game.ending() {
+        Some(ending) => {
+            let winner = game.winner().map_or("a draw".to_string(), |winner| format!("{:?} wins", winner));
+            format!("{}, {}", ending, winner)
+        },
+        None => "unfinished".to_string(),
+    };
+    println!("white {} black {}, {}", game.count(Player::White), game.count(Player::Black), result);
+    swings.sort_by_key(|&(swing, number, ..)| (std::cmp::Reverse(swing), number));
+    if !swings.is_empty() {
+        println!("largest swings:");
+    }
+    for (swing, number, player, pos, best) in swings.into_iter().take(5) {
+        println!("{:>3}. {:?} {} instead of {}, {} worse", number + 1, player, pos, best, swing);
+    }
     Ok(())
 }
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let mut args = Args::parse(args)?;
+    match command.as_str() {
+        "play" => {
+            let board = BoardOptions::parse(&mut args)?;
+            let white = args.option("white").unwrap_or_else(|| "human".to_string());
+            let black = args.option("black").unwrap_or_else(|| "human".to_string());
+            let games = args.parsed_option("games", 1)?;
+            if games == 0 {
+                return Err("--games must be at least 1".into());
+            }
+            let swap = args.flag("swap");
+            let save = args.option("save");
+            args.finish(0)?;
+            with_board_size!(board.size, play(&board, [&white, &black], games, swap, save.as_deref()))
+        },
+        "match" => {
+            let board = BoardOptions::parse(&mut args)?;
+            let games = args.parsed_option("games", 10)?;
+            let threads = args.parsed_option("threads", 1)?;
+            if games == 0 || threads == 0 {
+                return Err("--games and --threads must be at least 1".into());
+            }
+            let players = args.finish(2)?;
+            with_board_size!(board.size, play_match(&board, &players[0], &players[1], games, threads))
+        },
+        "sprt" => {
+            let board = BoardOptions::parse(&mut args)?;
+            let elo0 = args.parsed_option("elo0", 0.0)?;
+            let elo1 = args.parsed_option("elo1", 5.0)?;
+            let alpha = args.parsed_option("alpha", 0.05)?;
+            let beta = args.parsed_option("beta", 0.05)?;
+            let max_games = args.parsed_option("max-games", 20000)?;
+            let threads = args.parsed_option("threads", 1)?;
+            if elo0 >= elo1 {
+                return Err("--elo0 must be less than --elo1".into());
+            }
+            let probability = |p: f64| p > 0.0 && p < 1.0;
+            if !probability(alpha) || !probability(beta) {
+                return Err("--alpha and --beta must be between 0 and 1".into());
+            }
+            if max_games < 2 || threads == 0 {
+                return Err("--max-games must be at least 2 and --threads at least 1".into());
+            }
+            let players = args.finish(2)?;
+            let settings = TournamentSettings::new(players).config(board.match_config()).threads(threads);
+            let test = Sprt::new(settings, elo0, elo1).alpha(alpha).beta(beta).max_games(max_games);
+            with_board_size!(board.size, sprt(&board, test, max_games))
+        },
+        "validate" => {
+            let board = BoardOptions::parse(&mut args)?;
+            let path = args.finish(1)?;
+            with_board_size!(board.size, validate(&board, &path[0]))
+        },
+        "replay" => {
+            let path = args.finish(1)?;
+            let record = GameRecord::parse(&fs::read_to_string(&path[0])?)?;
+            with_board_size!(record.board_size()?, replay(&record))
+        },
+        "reproduce" => {
+            let arguments = args.finish(3)?;
+            let record = GameRecord::parse(&fs::read_to_string(&arguments[0])?)?;
+            let live = match arguments[1].as_str() {
+                "white" => Player::White,
+                "black" => Player::Black,
+                colour => return Err(format!("expected white or black, got {:?}", colour).into()),
+            };
+            with_board_size!(record.board_size()?, reproduce(&record, live, &arguments[2]))
+        },
+        "analyse" => {
+            let bot = args.option("bot");
+            let engine = Engine::from_name(&args.option("engine").unwrap_or_else(|| DEFAULT_ENGINE.to_string()))?;
+            let argument = args.finish(1)?;
+            if Path::new(&argument[0]).is_file() {
+                if bot.is_some() {
+                    return Err("--bot only works with a position".into());
+                }
+                let record = GameRecord::parse(&fs::read_to_string(&argument[0])?)?;
+                return with_board_size!(record.board_size()?, analyse_game(&record, engine));
+            }
+            let size = argument[0].split_once(':').map_or("", |(size, _)| size);
+            let size: usize = size.parse().map_err(|_| format!("invalid board size {:?}, or no such record file", size))?;
+            with_board_size!(size, analyse_position(&argument[0], bot.as_deref(), engine))
+        },
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        _ => Err(format!("unknown command {:?}\n\n{}", command, USAGE).into()),
+    }
+}
@@ -0,0 +1,322 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+use crate::game::{Ending, Game, Player, PlayerController, Pos, Rules, Turn};
+use crate::observer::GameObserver;
+use crate::opening::{play_opening, Opening};
+use crate::rng::Rng;
+
+/// How much time players get to answer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TimeControl {
+    #[default]
+    Unlimited,
+    /// Every move has to be answered within the limit.
+    PerMove(Duration),
+    /// Each player has this much time in total for all of its moves.
+    PerGame(Duration),
+}
+
+impl TimeControl {
+    fn check(&self, move_time: Duration, total_time: Duration) -> Result<(), Box<dyn Error>> {
+        match *self {
+            TimeControl::PerMove(limit) if move_time > limit =>
+                Err(format!("move took {:?}, limit is {:?}", move_time, limit).into()),
+            TimeControl::PerGame(limit) if total_time > limit =>
+                Err(format!("used {:?} in total, limit is {:?}", total_time, limit).into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What happens when a player errors, answers with an illegal move or runs out of time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MisplayPolicy {
+    /// The player loses the game.
+    #[default]
+    Forfeit,
+    /// A random legal move is played for the player and the game goes on.
+    RandomMove,
+}
+
+/// Settings for a match. The board size is the `N` of the `MatchRunner` it is used with.
+#[derive(Debug, Clone, Default)]
+pub struct MatchConfig {
+    pub rules: Rules,
+    /// Played from the initial position before the players take over.
+    pub opening: Option<Opening>,
+    pub time_control: TimeControl,
+    pub misplay_policy: MisplayPolicy,
+    /// Seeds everything random in the match, e.g. the moves picked by `MisplayPolicy::RandomMove`.
+    pub seed: u64,
+    /// Lets players take back moves with `Turn::Undo`, e.g. for humans
+    /// playing casually. Otherwise undoing is a misplay.
+    pub allow_undo: bool,
+}
+
+impl MatchConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn opening(mut self, opening: Opening) -> Self {
+        self.opening = Some(opening);
+        self
+    }
+
+    pub fn time_control(mut self, time_control: TimeControl) -> Self {
+        self.time_control = time_control;
+        self
+    }
+
+    pub fn misplay_policy(mut self, misplay_policy: MisplayPolicy) -> Self {
+        self.misplay_policy = misplay_policy;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn allow_undo(mut self, allow_undo: bool) -> Self {
+        self.allow_undo = allow_undo;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misplay {
+    pub player: Player,
+    /// Number of moves played before the misplay.
+    pub move_number: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawOffer {
+    pub player: Player,
+    /// Number of moves played before the offer.
+    pub move_number: usize,
+    pub accepted: bool,
+}
+
+/// Everything that happened in a match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    pub board_size: usize,
+    pub rules: Rules,
+    pub seed: u64,
+    /// The opening the match started from. Its moves are also the first moves of `moves`.
+    pub opening: Option<Opening>,
+    /// All moves of the game as offsets. Passes are implicit.
+    pub moves: Vec<u8>,
+    pub winner: Option<Player>,
+    pub ending: Ending,
+    /// The player that lost by misplaying, if any.
+    pub forfeit: Option<Player>,
+    pub misplays: Vec<Misplay>,
+    pub draw_offers: Vec<DrawOffer>,
+    pub white_count: usize,
+    pub black_count: usize,
+    pub white_time: Duration,
+    pub black_time: Duration,
+}
+
+impl MatchRecord {
+    /// Rebuilds the final position of the match.
+    pub fn to_game<const N: usize>(&self) -> Game<N> {
+        assert_eq!(self.board_size, N);
+        let mut game = Game::with_rules(self.rules);
+        for &offset in &self.moves {
+            if game.legal_moves(game.current_player()).is_empty() {
+                game.skip();
+            }
+            game.play(Pos::from_offset(offset, N));
+        }
+        match self.ending {
+            Ending::Completed => {},
+            Ending::Misplay(player) => game.misplay(player),
+            Ending::Resignation(player) => game.resign(player),
+            Ending::DrawAgreement => game.agree_draw(),
+        }
+        game
+    }
+}
+
+/// Plays matches between two players according to a `MatchConfig`.
+pub struct MatchRunner<'a, const N: usize> {
+    config: MatchConfig,
+    start: Game<N>,
+    observers: Vec<Box<dyn GameObserver<N> + 'a>>,
+}
+
+impl<'a, const N: usize> MatchRunner<'a, N> {
+    /// Fails if the opening in `config` is not a sequence of legal moves.
+    pub fn new(config: MatchConfig) -> Result<Self, Box<dyn Error>> {
+        let moves = config.opening.as_ref().map_or(&[][..], |opening| &opening.moves);
+        let start = play_opening::<N>(moves, config.rules)?;
+        Ok(Self { config, start, observers: Vec::new() })
+    }
+
+    pub fn observer(mut self, observer: Box<dyn GameObserver<N> + 'a>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    pub fn config(&self) -> &MatchConfig {
+        &self.config
+    }
+
+    pub fn run(&mut self, white_player: &mut dyn PlayerController<N>, black_player: &mut dyn PlayerController<N>) -> MatchRecord {
+        let mut game = self.start.clone();
+        let mut rng = Rng::new(self.config.seed);
+        let mut misplays = Vec::new();
+        let mut draw_offers = Vec::new();
+        let mut white_time = Duration::ZERO;
+        let mut black_time = Duration::ZERO;
+        while !game.game_over() {
+            let player = game.current_player();
+            let legal_moves = game.legal_moves(player);
+            if legal_moves.is_empty() {
+                game.skip();
+                for observer in &mut self.observers {
+                    observer.on_pass(&game, player);
+                }
+                // Game is over if neither can play
+                assert!(!game.legal_moves(game.current_player()).is_empty());
+                continue;
+            }
+            for observer in &mut self.observers {
+                observer.on_turn_start(&game);
+            }
+
+            let started = Instant::now();
+            let mut offered_draw = false;
+            let mut refused_undo = false;
+            // An accepted draw offer comes out of this as `Turn::OfferDraw`
+            let play = loop {
+                let (mover, opponent): (&mut dyn PlayerController<N>, &mut dyn PlayerController<N>) = if player == Player::White {
+                    (&mut *white_player, &mut *black_player)
+                } else {
+                    (&mut *black_player, &mut *white_player)
+                };
+                match mover.take_turn(&game) {
+                    Ok(Turn::OfferDraw) if offered_draw => break Err("offered a draw twice in one turn".into()),
+                    Ok(Turn::OfferDraw) => {
+                        offered_draw = true;
+                        let accepted = opponent.accept_draw(&game);
+                        draw_offers.push(DrawOffer { player, move_number: game.move_list().len(), accepted });
+                        for observer in &mut self.observers {
+                            observer.on_draw_offer(&game, player, accepted);
+                        }
+                        if accepted {
+                            break Ok(Turn::OfferDraw);
+                        }
+                    },
+                    Ok(Turn::Undo) if !self.config.allow_undo => break Err("taking back moves is not allowed".into()),
+                    Ok(Turn::Undo) if self.undone(&game, player).is_some() => break Ok(Turn::Undo),
+                    // Nothing of its own to take back, so it is asked again
+                    Ok(Turn::Undo) if refused_undo => break Err("took back a move twice with none to take back".into()),
+                    Ok(Turn::Undo) => refused_undo = true,
+                    turn => break turn,
+                }
+            };
+            let move_time = started.elapsed();
+            let total_time = if player == Player::White {
+                white_time += move_time;
+                white_time
+            } else {
+                black_time += move_time;
+                black_time
+            };
+            let play = play
+                .and_then(|turn| match turn {
+                    Turn::Move(pos) if !legal_moves.contains(&pos) => Err(format!("illegal move {}", pos).into()),
+                    turn => Ok(turn),
+                })
+                .and_then(|turn| self.config.time_control.check(move_time, total_time).map(|_| turn));
+
+            match play {
+                Ok(Turn::Move(pos)) => {
+                    game.play(pos);
+                    for observer in &mut self.observers {
+                        observer.on_move(&game, player, pos);
+                    }
+                },
+                Ok(Turn::Resign) => {
+                    game.resign(player);
+                    for observer in &mut self.observers {
+                        observer.on_resign(&game, player);
+                    }
+                },
+                Ok(Turn::OfferDraw) => game.agree_draw(),
+                Ok(Turn::Undo) => {
+                    game = self.undone(&game, player).expect("checked when asked");
+                    for observer in &mut self.observers {
+                        observer.on_undo(&game, player);
+                    }
+                },
+                Err(err) => {
+                    misplays.push(Misplay { player, move_number: game.move_list().len(), reason: err.to_string() });
+                    match self.config.misplay_policy {
+                        MisplayPolicy::Forfeit => {
+                            game.misplay(player);
+                            for observer in &mut self.observers {
+                                observer.on_misplay(&game, player, err.as_ref());
+                            }
+                        },
+                        MisplayPolicy::RandomMove => {
+                            for observer in &mut self.observers {
+                                observer.on_misplay(&game, player, err.as_ref());
+                            }
+                            let pos = legal_moves[rng.below(legal_moves.len())];
+                            game.play(pos);
+                            for observer in &mut self.observers {
+                                observer.on_move(&game, player, pos);
+                            }
+                        },
+                    }
+                }
+            };
+        }
+        for observer in &mut self.observers {
+            observer.on_game_over(&game);
+        }
+
+        MatchRecord {
+            board_size: N,
+            rules: self.config.rules,
+            seed: self.config.seed,
+            opening: self.config.opening.clone(),
+            moves: game.move_list().clone(),
+            winner: game.winner(),
+            ending: game.ending().expect("game is over"),
+            forfeit: game.misplayed_by(),
+            misplays,
+            draw_offers,
+            white_count: game.count(Player::White),
+            black_count: game.count(Player::Black),
+            white_time,
+            black_time,
+        }
+    }
+
+    // `game` with moves taken back until `player` is to move again, having
+    // undone its own last move. `None` if that would undo the opening.
+    fn undone(&self, game: &Game<N>, player: Player) -> Option<Game<N>> {
+        let mut earlier = game.clone();
+        loop {
+            if !earlier.undo() || earlier.move_list().len() < self.start.move_list().len() {
+                return None;
+            }
+            if earlier.current_player() == player {
+                return Some(earlier);
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+use crate::game::{Game, Player, PlayerController, Pos};
+use crate::rng::Rng;
+
+// Weight of exploring rarely visited moves against playing the best ones, in UCT
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// How long `MctsPlayer` searches before moving.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MctsBudget {
+    /// A fixed number of playouts, the same search every time for a given seed.
+    Playouts(u32),
+    /// As many playouts as fit in the time.
+    Time(Duration),
+}
+
+/// Monte Carlo tree search with UCT. Plays random games from the current
+/// position, growing a tree of the moves that have done best so far, and
+/// plays the move that was searched the most. Needs no knowledge of the game
+/// beyond its rules, so it plays the same on every board size.
+pub struct MctsPlayer {
+    budget: MctsBudget,
+    rng: Rng,
+}
+
+// A position in the search tree, reached by `pos` (`None` for a pass)
+struct Node {
+    pos: Option<Pos>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Option<Pos>>,
+    // The player who made the move leading here, whose wins are counted
+    player: Player,
+    visits: u32,
+    wins: f64,
+}
+
+impl MctsPlayer {
+    /// Searches 1000 playouts per move.
+    pub fn new(seed: u64) -> Self {
+        Self { budget: MctsBudget::Playouts(1000), rng: Rng::new(seed) }
+    }
+
+    pub fn playouts(mut self, playouts: u32) -> Self {
+        assert!(playouts > 0, "need at least one playout");
+        self.budget = MctsBudget::Playouts(playouts);
+        self
+    }
+
+    pub fn time_budget(mut self, time: Duration) -> Self {
+        self.budget = MctsBudget::Time(time);
+        self
+    }
+
+    fn search<const N: usize>(&mut self, game: &Game<N>) -> Vec<Node> {
+        let start = Instant::now();
+        let mut tree = vec![Node {
+            pos: None,
+            parent: None,
+            children: Vec::new(),
+            untried: untried_moves(game),
+            player: game.current_player().flip(),
+            visits: 0,
+            wins: 0.0,
+        }];
+        let mut playouts = 0;
+        while match self.budget {
+            MctsBudget::Playouts(budget) => playouts < budget,
+            MctsBudget::Time(budget) => playouts == 0 || start.elapsed() < budget,
+        } {
+            playouts += 1;
+            let mut game = game.clone();
+
+            // Select down the tree while every move of a node has been tried
+            let mut node = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = self.select_child(&tree, node);
+                play(&mut game, tree[node].pos);
+            }
+
+            // Expand with a random untried move
+            if !tree[node].untried.is_empty() {
+                let untried = &mut tree[node].untried;
+                let pos = untried.swap_remove(self.rng.below(untried.len()));
+                let player = game.current_player();
+                play(&mut game, pos);
+                tree.push(Node {
+                    pos,
+                    parent: Some(node),
+                    children: Vec::new(),
+                    untried: untried_moves(&game),
+                    player,
+                    visits: 0,
+                    wins: 0.0,
+                });
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // Play out randomly and count the result back up the tree
+            if !game.game_over() {
+                loop {
+                    let moves = game.legal_moves(game.current_player());
+                    if !moves.is_empty() {
+                        game.play(moves[self.rng.below(moves.len())]);
+                    } else if game.legal_moves(game.current_player().flip()).is_empty() {
+                        break;
+                    } else {
+                        game.skip();
+                    }
+                }
+            }
+            let winner = game.winner();
+            let mut current = Some(node);
+            while let Some(index) = current {
+                let node = &mut tree[index];
+                node.visits += 1;
+                node.wins += match winner {
+                    Some(player) if player == node.player => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                };
+                current = node.parent;
+            }
+        }
+        tree
+    }
+
+    // The child with the highest upper confidence bound, ties broken randomly
+    fn select_child(&mut self, tree: &[Node], node: usize) -> usize {
+        let log_visits = (tree[node].visits as f64).ln();
+        let mut best = Vec::new();
+        let mut best_score = f64::NEG_INFINITY;
+        for &child in &tree[node].children {
+            let child_node = &tree[child];
+            let visits = child_node.visits as f64;
+            let score = child_node.wins / visits + EXPLORATION * (log_visits / visits).sqrt();
+            if score > best_score {
+                best_score = score;
+                best.clear();
+            }
+            if score == best_score {
+                best.push(child);
+            }
+        }
+        best[self.rng.below(best.len())]
+    }
+}
+
+impl<const N: usize> PlayerController<N> for MctsPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        let moves = game.legal_moves(game.current_player());
+        match moves.len() {
+            0 => return Err("no legal moves".into()),
+            1 => return Ok(moves[0]),
+            _ => {},
+        }
+        let tree = self.search(game);
+        tree[0].children.iter()
+            .max_by_key(|&&child| tree[child].visits)
+            .and_then(|&child| tree[child].pos)
+            .ok_or_else(|| "search found no move".into())
+    }
+}
+
+// Every legal move, or a pass if there are none and the game goes on
+fn untried_moves<const N: usize>(game: &Game<N>) -> Vec<Option<Pos>> {
+    if game.game_over() {
+        return Vec::new();
+    }
+    let moves = game.legal_moves(game.current_player());
+    if moves.is_empty() {
+        vec![None]
+    } else {
+        moves.into_iter().map(Some).collect()
+    }
+}
+
+fn play<const N: usize>(game: &mut Game<N>, pos: Option<Pos>) {
+    match pos {
+        Some(pos) => game.play(pos),
+        None => game.skip(),
+    }
+}
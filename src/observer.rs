@@ -0,0 +1,74 @@
+use std::error::Error;
+use crate::game::{Ending, Game, Player, Pos};
+
+/// Receives events as a game is played. All methods do nothing by default.
+pub trait GameObserver<const N: usize> {
+    /// `game.current_player()` is about to be asked for a move.
+    fn on_turn_start(&mut self, _game: &Game<N>) {}
+    /// `player` placed a tile at `pos`, `game` is the position after the move.
+    fn on_move(&mut self, _game: &Game<N>, _player: Player, _pos: Pos) {}
+    /// `player` had no legal moves and was skipped.
+    fn on_pass(&mut self, _game: &Game<N>, _player: Player) {}
+    /// `player` failed to produce a legal move in time. Depending on the
+    /// `MisplayPolicy` it either forfeits or has a random move played for it.
+    fn on_misplay(&mut self, _game: &Game<N>, _player: Player, _error: &dyn Error) {}
+    /// `player`, who is to move, offered a draw and the opponent answered.
+    fn on_draw_offer(&mut self, _game: &Game<N>, _player: Player, _accepted: bool) {}
+    fn on_resign(&mut self, _game: &Game<N>, _player: Player) {}
+    /// `player` took back moves, `game` is the position it is asked to move in again.
+    fn on_undo(&mut self, _game: &Game<N>, _player: Player) {}
+    fn on_game_over(&mut self, _game: &Game<N>) {}
+}
+
+/// Ignores all events.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SilentObserver;
+
+impl<const N: usize> GameObserver<N> for SilentObserver {}
+
+/// Prints the game to stdout as it is played.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TerminalObserver;
+
+impl<const N: usize> GameObserver<N> for TerminalObserver {
+    fn on_turn_start(&mut self, game: &Game<N>) {
+        if game.current_player() == Player::White {
+            println!("White (1) player's turn");
+        } else {
+            println!("Black (2) player's turn");
+        }
+        game.print();
+    }
+
+    fn on_move(&mut self, _game: &Game<N>, player: Player, pos: Pos) {
+        println!("{:?} placed {}", player, pos);
+    }
+
+    fn on_pass(&mut self, _game: &Game<N>, player: Player) {
+        println!("{:?} has no legal moves and passes", player);
+    }
+
+    fn on_misplay(&mut self, _game: &Game<N>, player: Player, error: &dyn Error) {
+        println!("{:?} misplayed: {}", player, error);
+    }
+
+    fn on_draw_offer(&mut self, _game: &Game<N>, player: Player, accepted: bool) {
+        println!("{:?} offers a draw, {}", player, if accepted { "accepted" } else { "declined" });
+    }
+
+    fn on_resign(&mut self, _game: &Game<N>, player: Player) {
+        println!("{:?} resigns", player);
+    }
+
+    fn on_undo(&mut self, _game: &Game<N>, player: Player) {
+        println!("{:?} takes back a move", player);
+    }
+
+    fn on_game_over(&mut self, game: &Game<N>) {
+        game.print();
+        if let Some(ending @ (Ending::Resignation(_) | Ending::DrawAgreement)) = game.ending() {
+            println!("{}", ending);
+        }
+        println!("Winner: {:?}", game.winner());
+    }
+}
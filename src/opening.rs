@@ -0,0 +1,140 @@
+use std::error::Error;
+use crate::game::{Game, Player, Pos, Rules};
+use crate::rng::Rng;
+
+// Random openings are only kept if neither side is ahead by more than this
+// in tiles or number of legal moves.
+const MAX_COUNT_DIFFERENCE: usize = 2;
+const MAX_MOBILITY_DIFFERENCE: usize = 2;
+
+/// Moves played from the initial position before the players take over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub id: String,
+    /// Moves as offsets.
+    pub moves: Vec<u8>,
+}
+
+impl Opening {
+    /// Creates an opening identified by its move list, e.g. `26-34-42-33`.
+    pub fn new(moves: Vec<u8>) -> Self {
+        let id = moves.iter().map(|m| m.to_string()).collect::<Vec<_>>().join("-");
+        Self { id, moves }
+    }
+
+    pub fn with_id(id: String, moves: Vec<u8>) -> Self {
+        Self { id, moves }
+    }
+}
+
+/// A set of starting positions to play matches from. Deterministic bots
+/// always play the same game from the same position, so playing a pairing
+/// from several openings, each with both colours, gives more meaningful results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpeningSuite {
+    openings: Vec<Opening>,
+}
+
+impl OpeningSuite {
+    pub fn new(openings: Vec<Opening>) -> Self {
+        Self { openings }
+    }
+
+    /// Generates up to `count` distinct openings of `plies` random moves that
+    /// leave a roughly balanced position. The same arguments always give the same suite.
+    pub fn random<const N: usize>(count: usize, plies: usize, rules: Rules, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut openings: Vec<Opening> = Vec::new();
+        let mut attempts = 0;
+        while openings.len() < count && attempts < count * 100 {
+            attempts += 1;
+            let mut game = Game::<N>::with_rules(rules);
+            while game.move_list().len() < plies && !game.game_over() {
+                let legal_moves = game.legal_moves(game.current_player());
+                if legal_moves.is_empty() {
+                    game.skip();
+                    continue;
+                }
+                game.play(legal_moves[rng.below(legal_moves.len())]);
+            }
+            let moves = game.move_list();
+            if moves.len() < plies || !is_balanced(&game) || openings.iter().any(|o| &o.moves == moves) {
+                continue;
+            }
+            openings.push(Opening::new(moves.clone()));
+        }
+        Self { openings }
+    }
+
+    /// Parses one opening per line, as offsets separated by whitespace or
+    /// commas, optionally preceded by an id and a colon. Blank lines and lines
+    /// starting with `#` are ignored. Every opening is checked to be legal.
+    ///
+    /// ```text
+    /// # 8x8 openings
+    /// first: 26 34 42 33
+    /// 37, 43, 34, 29
+    /// ```
+    pub fn parse<const N: usize>(text: &str, rules: Rules) -> Result<Self, Box<dyn Error>> {
+        let mut openings = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, moves) = match line.split_once(':') {
+                Some((id, moves)) => (Some(id.trim().to_string()), moves),
+                None => (None, line),
+            };
+            let moves = moves.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|m| !m.is_empty())
+                .map(|m| m.parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("line {}: {}", i + 1, err))?;
+            play_opening::<N>(&moves, rules).map_err(|err| format!("line {}: {}", i + 1, err))?;
+            openings.push(match id {
+                Some(id) => Opening::with_id(id, moves),
+                None => Opening::new(moves),
+            });
+        }
+        Ok(Self { openings })
+    }
+
+    pub fn openings(&self) -> &[Opening] {
+        &self.openings
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+}
+
+fn is_balanced<const N: usize>(game: &Game<N>) -> bool {
+    let count_difference = game.count(Player::White).abs_diff(game.count(Player::Black));
+    let mobility_difference = game.legal_moves(Player::White).len()
+        .abs_diff(game.legal_moves(Player::Black).len());
+    !game.game_over() && count_difference <= MAX_COUNT_DIFFERENCE && mobility_difference <= MAX_MOBILITY_DIFFERENCE
+}
+
+/// Plays `moves` from the initial position, failing if any of them is illegal.
+pub(crate) fn play_opening<const N: usize>(moves: &[u8], rules: Rules) -> Result<Game<N>, Box<dyn Error>> {
+    let mut game = Game::<N>::with_rules(rules);
+    for &offset in moves {
+        if game.game_over() {
+            return Err("opening continues after the game is over".into());
+        }
+        if game.legal_moves(game.current_player()).is_empty() {
+            game.skip();
+        }
+        let pos = Pos::from_offset(offset, N);
+        if !game.legal_moves(game.current_player()).contains(&pos) {
+            return Err(format!("illegal opening move {}", pos).into());
+        }
+        game.play(pos);
+    }
+    Ok(game)
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use crate::game::{Game, PlayerController, Pos, Turn};
+use crate::textprotocol::TextProtocol;
+
+// How long a bot gets to start up and answer the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A bot running as a local executable, speaking the line protocol described
+/// in the README over its stdin and stdout. Its stderr is passed through, so
+/// it can be used for logging.
+pub struct ProcessPlayer {
+    child: Child,
+    protocol: TextProtocol,
+    timeout: Duration,
+}
+
+impl ProcessPlayer {
+    /// Starts `command` and waits for it to complete the handshake. Answers
+    /// time out after 10 seconds by default.
+    pub fn spawn(command: &mut Command) -> Result<Self, Box<dyn Error>> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("could not start {:?}: {}", command.get_program(), err))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        match TextProtocol::connect(stdout, stdin, HANDSHAKE_TIMEOUT) {
+            Ok(protocol) => Ok(Self { child, protocol, timeout: Duration::from_secs(10) }),
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(err)
+            },
+        }
+    }
+
+    /// How long the bot gets to answer each request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The name the bot gave in the handshake, possibly empty.
+    pub fn name(&self) -> &str {
+        self.protocol.name()
+    }
+}
+
+impl<const N: usize> PlayerController<N> for ProcessPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        match self.take_turn(game)? {
+            Turn::Move(pos) => Ok(pos),
+            turn => Err(format!("expected a move, got {:?}", turn).into()),
+        }
+    }
+
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
+        self.protocol.take_turn(game, self.timeout)
+    }
+
+    fn accept_draw(&mut self, game: &Game<N>) -> bool {
+        self.protocol.accept_draw(game, self.timeout).unwrap_or(false)
+    }
+}
+
+impl Drop for ProcessPlayer {
+    fn drop(&mut self) {
+        self.protocol.quit();
+        // Give the bot a moment to exit by itself before killing it
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
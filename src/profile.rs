@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Fuel and wall clock time a bot spent answering a single move.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MoveProfile {
+    pub fuel: u64,
+    pub time: Duration,
+}
+
+/// How often a function was the innermost frame when a sample was taken,
+/// at evenly spread points in the measured time of each move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub samples: u64,
+}
+
+/// Per-game execution profile of a bot, collected by a profiling `WasmPlayer`.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub moves: Vec<MoveProfile>,
+    function_samples: HashMap<String, u64>,
+}
+
+impl ProfileReport {
+    pub(crate) fn record_move(&mut self, fuel: u64, time: Duration) {
+        self.moves.push(MoveProfile { fuel, time });
+    }
+
+    pub(crate) fn record_sample(&mut self, function: String) {
+        *self.function_samples.entry(function).or_insert(0) += 1;
+    }
+
+    pub fn total_fuel(&self) -> u64 {
+        self.moves.iter().map(|m| m.fuel).sum()
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.moves.iter().map(|m| m.time).sum()
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.function_samples.values().sum()
+    }
+
+    /// Functions ordered by number of samples, hottest first.
+    pub fn hottest_functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: Vec<FunctionProfile> = self.function_samples.iter()
+            .map(|(name, &samples)| FunctionProfile { name: name.clone(), samples })
+            .collect();
+        functions.sort_by(|a, b| b.samples.cmp(&a.samples).then_with(|| a.name.cmp(&b.name)));
+        functions
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "moves: {}, fuel: {}, time: {:?}", self.moves.len(), self.total_fuel(), self.total_time())?;
+        writeln!(f)?;
+        writeln!(f, "hottest functions ({} samples):", self.total_samples())?;
+        let total = self.total_samples().max(1) as f64;
+        for function in self.hottest_functions() {
+            writeln!(f, "{:>6.2}% {:>8} {}", function.samples as f64 * 100.0 / total, function.samples, function.name)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "per move:")?;
+        for (i, m) in self.moves.iter().enumerate() {
+            writeln!(f, "{:>4} {:>12} {:?}", i, m.fuel, m.time)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::f64::consts::PI;
+
+// Rating new players start at, for both Elo and Glicko-2
+const INITIAL_RATING: f64 = 1500.0;
+// Rating deviation of new players, also the largest deviation
+const INITIAL_DEVIATION: f64 = 350.0;
+const INITIAL_VOLATILITY: f64 = 0.06;
+
+// Converts between the Glicko and Glicko-2 scales
+const GLICKO2_SCALE: f64 = 173.7178;
+// Convergence tolerance of the volatility iteration
+const EPSILON: f64 = 0.000001;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Elo {
+    pub rating: f64,
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Self { rating: INITIAL_RATING }
+    }
+}
+
+impl Elo {
+    /// How much a single game can move a rating.
+    pub const K: f64 = 32.0;
+
+    /// Expected score against `opponent`, between 0 and 1.
+    pub fn expected_score(&self, opponent: &Elo) -> f64 {
+        1.0 / (1.0 + 10f64.powf((opponent.rating - self.rating) / 400.0))
+    }
+
+    /// Updates both ratings after a game where `self` scored `score`.
+    pub fn update(&mut self, opponent: &mut Elo, score: f64) {
+        let expected = self.expected_score(opponent);
+        self.rating += Self::K * (score - expected);
+        opponent.rating -= Self::K * (score - expected);
+    }
+}
+
+/// Glicko-2 rating. Unlike Elo it tracks how certain the rating is, so new
+/// bots converge quickly and the deviation tells how much to trust a rating.
+/// Results are applied per rating period, against the opponents' ratings from
+/// before the period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glicko2 {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Glicko2 {
+    fn default() -> Self {
+        Self { rating: INITIAL_RATING, deviation: INITIAL_DEVIATION, volatility: INITIAL_VOLATILITY }
+    }
+}
+
+impl Glicko2 {
+    /// Constrains how much the volatility can change, smaller is more stable.
+    pub const TAU: f64 = 0.5;
+
+    /// Expected score against `opponent`, between 0 and 1.
+    pub fn expected_score(&self, opponent: &Glicko2) -> f64 {
+        expected(self.mu(), opponent.mu(), g(opponent.phi()))
+    }
+
+    fn mu(&self) -> f64 {
+        (self.rating - INITIAL_RATING) / GLICKO2_SCALE
+    }
+
+    fn phi(&self) -> f64 {
+        self.deviation / GLICKO2_SCALE
+    }
+
+    /// Rating after a period in which the player played `results`, pairs of
+    /// opponent and score. Without games only the deviation grows.
+    pub fn updated(&self, results: &[(Glicko2, f64)]) -> Glicko2 {
+        let (mu, phi, sigma) = (self.mu(), self.phi(), self.volatility);
+        if results.is_empty() {
+            let deviation = (phi * phi + sigma * sigma).sqrt() * GLICKO2_SCALE;
+            return Glicko2 { deviation: deviation.min(INITIAL_DEVIATION), ..*self };
+        }
+
+        let mut v_inverse = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in results {
+            let g = g(opponent.phi());
+            let e = expected(mu, opponent.mu(), g);
+            v_inverse += g * g * e * (1.0 - e);
+            improvement += g * (score - e);
+        }
+        let v = 1.0 / v_inverse;
+        let delta = v * improvement;
+
+        let sigma = new_volatility(phi, sigma, v, delta);
+        let phi_star = (phi * phi + sigma * sigma).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Glicko2 {
+            rating: mu * GLICKO2_SCALE + INITIAL_RATING,
+            deviation: (phi * GLICKO2_SCALE).min(INITIAL_DEVIATION),
+            volatility: sigma,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn expected(mu: f64, opponent_mu: f64, g: f64) -> f64 {
+    1.0 / (1.0 + (-g * (mu - opponent_mu)).exp())
+}
+
+// Finds the new volatility with the Illinois algorithm, as in step 5 of
+// Glickman's "Example of the Glicko-2 system".
+fn new_volatility(phi: f64, sigma: f64, v: f64, delta: f64) -> f64 {
+    let tau = Glicko2::TAU;
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let denominator = phi * phi + v + ex;
+        ex * (delta * delta - phi * phi - v - ex) / (2.0 * denominator * denominator) - (x - a) / (tau * tau)
+    };
+
+    let mut x_a = a;
+    let mut x_b = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * tau) < 0.0 {
+            k += 1.0;
+        }
+        a - k * tau
+    };
+    let (mut f_a, mut f_b) = (f(x_a), f(x_b));
+    while (x_b - x_a).abs() > EPSILON {
+        let x_c = x_a + (x_a - x_b) * f_a / (f_b - f_a);
+        let f_c = f(x_c);
+        if f_c * f_b <= 0.0 {
+            x_a = x_b;
+            f_a = f_b;
+        } else {
+            f_a /= 2.0;
+        }
+        x_b = x_c;
+        f_b = f_c;
+    }
+    (x_a / 2.0).exp()
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::game::{Ending, Game, Player, Pos, Rules};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::matchrunner::MatchRecord;
+
+// Movetext lines are wrapped after this many characters
+const LINE_WIDTH: usize = 79;
+
+/// A move or pass in a `GameRecord`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMove {
+    /// `None` for a pass.
+    pub pos: Option<Pos>,
+    pub comment: Option<String>,
+}
+
+/// Text format for archiving and sharing games, modelled after PGN. A header
+/// of tags is followed by the moves, black first, in algebraic notation (see
+/// `Pos`). Passes are written out, comments go in braces after the move they
+/// are about, and the result ends the game. Moves in the older `x,y`
+/// coordinates, counting from 0 in the top left corner, are still read.
+///
+/// ```text
+/// [White "hello"]
+/// [Black "human"]
+/// [Size "8"]
+/// [Rules "orthogonal"]
+/// [Date "2022.06.01"]
+/// [Result "1-0"]
+/// [Termination "normal"]
+///
+/// 1. e6 f4 {a comment} 2. e3 d6 3. c4 d3 ... 29. c8 a1 1-0
+/// ```
+///
+/// `Size` is required to load the game. `Rules` defaults to orthogonal, and
+/// `Termination` is one of `normal`, `misplay`, `resignation` or `agreement`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameRecord {
+    /// Tags in the order they appear in, e.g. `("White", "hello")`.
+    pub tags: Vec<(String, String)>,
+    /// Comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<RecordedMove>,
+}
+
+impl GameRecord {
+    /// Records `game` as played so far, dated today.
+    pub fn from_game<const N: usize>(game: &Game<N>, white: &str, black: &str) -> Self {
+        let mut record = GameRecord::default();
+        record.set_tag("White", white);
+        record.set_tag("Black", black);
+        record.set_tag("Size", &N.to_string());
+        record.set_tag("Rules", rules_name(game.rules()));
+        record.set_tag("Date", &today());
+        record.set_tag("Result", &result_string(game));
+        if let Some(ending) = game.ending() {
+            record.set_tag("Termination", termination_name(ending));
+        }
+
+        // Passes aren't in the move list, so replay it to find them
+        let mut replay = Game::<N>::with_rules(game.rules());
+        for &offset in game.move_list() {
+            if replay.legal_moves(replay.current_player()).is_empty() {
+                replay.skip();
+                record.moves.push(RecordedMove { pos: None, comment: None });
+            }
+            let pos = Pos::from_offset(offset, N);
+            replay.play(pos);
+            record.moves.push(RecordedMove { pos: Some(pos), comment: None });
+        }
+        record
+    }
+
+    /// Records a finished match, including the opening it was played from.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_match_record<const N: usize>(match_record: &MatchRecord, white: &str, black: &str) -> Self {
+        let mut record = Self::from_game(&match_record.to_game::<N>(), white, black);
+        if let Some(opening) = &match_record.opening {
+            record.set_tag("Opening", &opening.id);
+        }
+        record
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of `name`, or adds it at the end.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn board_size(&self) -> Result<usize, Box<dyn Error>> {
+        let size = self.tag("Size").ok_or("missing Size tag")?;
+        Ok(size.parse().map_err(|err| format!("invalid Size {:?}: {}", size, err))?)
+    }
+
+    pub fn rules(&self) -> Result<Rules, Box<dyn Error>> {
+        match self.tag("Rules") {
+            None | Some("orthogonal") => Ok(Rules::Orthogonal),
+            Some("standard") => Ok(Rules::Standard),
+            Some(rules) => Err(format!("unknown Rules {:?}", rules).into()),
+        }
+    }
+
+    /// Replays the moves, failing on illegal moves and passes. A game that
+    /// ended by misplay, resignation or agreement ends the same way.
+    pub fn to_game<const N: usize>(&self) -> Result<Game<N>, Box<dyn Error>> {
+        let size = self.board_size()?;
+        if size != N {
+            return Err(format!("record is for a {}x{} board, not {}x{}", size, size, N, N).into());
+        }
+        let mut game = Game::<N>::with_rules(self.rules()?);
+        for (i, recorded) in self.moves.iter().enumerate() {
+            if game.game_over() {
+                return Err(format!("move {}: the game is already over", i + 1).into());
+            }
+            let legal_moves = game.legal_moves(game.current_player());
+            match recorded.pos {
+                None if legal_moves.is_empty() => game.skip(),
+                None => return Err(format!("move {}: pass while there are legal moves", i + 1).into()),
+                Some(pos) if legal_moves.contains(&pos) => game.play(pos),
+                Some(pos) => return Err(format!("move {}: illegal move {}", i + 1, pos).into()),
+            }
+        }
+
+        let loser = match self.tag("Result") {
+            Some("1-0") => Some(Player::Black),
+            Some("0-1") => Some(Player::White),
+            _ => None,
+        };
+        match (self.tag("Termination"), loser) {
+            (None | Some("normal"), _) => {},
+            (Some("agreement"), _) => game.agree_draw(),
+            (Some("misplay"), Some(loser)) => game.misplay(loser),
+            (Some("resignation"), Some(loser)) => game.resign(loser),
+            (Some(termination), _) => return Err(format!("invalid Termination {:?} for the Result", termination).into()),
+        }
+        Ok(game)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut record = GameRecord::default();
+        let mut lines = text.lines().enumerate().peekable();
+        while let Some((i, line)) = lines.peek() {
+            let line = line.trim();
+            if line.is_empty() {
+                lines.next();
+                continue;
+            }
+            if !line.starts_with('[') {
+                break;
+            }
+            let (name, value) = parse_tag(line).map_err(|err| format!("line {}: {}", i + 1, err))?;
+            record.tags.push((name, value));
+            lines.next();
+        }
+
+        // Without a Size tag, moves can go up to the last column with a letter
+        let size = record.board_size().unwrap_or(26);
+        let movetext: Vec<&str> = lines.map(|(_, line)| line).collect();
+        let movetext = movetext.join("\n");
+        let mut chars = movetext.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == '{' {
+                chars.next();
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                match record.moves.last_mut() {
+                    Some(last) => last.comment = Some(comment),
+                    None => record.comment = Some(comment),
+                }
+                continue;
+            }
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '{' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            match token.as_str() {
+                "1-0" | "0-1" | "1/2-1/2" | "*" => break,
+                "pass" => record.moves.push(RecordedMove { pos: None, comment: None }),
+                _ if is_move_number(&token) => {},
+                _ => {
+                    let pos = parse_pos(&token, size).ok_or_else(|| format!("invalid move {:?}", token))?;
+                    record.moves.push(RecordedMove { pos: Some(pos), comment: None });
+                },
+            }
+        }
+        Ok(record)
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        for (i, recorded) in self.moves.iter().enumerate() {
+            if i % 2 == 0 {
+                tokens.push(format!("{}.", i / 2 + 1));
+            }
+            tokens.push(recorded.pos.map_or("pass".to_string(), |pos| pos.to_string()));
+            if let Some(comment) = &recorded.comment {
+                tokens.push(format!("{{{}}}", comment));
+            }
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+// Algebraic notation, or `x,y` from 0 as in older records. Either has to be
+// on a board of `size`.
+fn parse_pos(token: &str, size: usize) -> Option<Pos> {
+    let pos: Pos = match token.split_once(',') {
+        Some((x, y)) => Pos::new(x.parse().ok()?, y.parse().ok()?),
+        None => token.parse().ok()?,
+    };
+    (pos.x() < size && pos.y() < size).then_some(pos)
+}
+
+fn is_move_number(token: &str) -> bool {
+    let digits = token.trim_end_matches('.');
+    digits.len() < token.len() && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+// [Name "value"], where the value may contain \" and \\
+fn parse_tag(line: &str) -> Result<(String, String), Box<dyn Error>> {
+    let inner = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')).ok_or("tag must be enclosed in []")?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or("tag has no value")?;
+    let value = value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or("tag value must be quoted")?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' { chars.next().ok_or("dangling \\")? } else { c });
+    }
+    Ok((name.to_string(), unescaped))
+}
+
+fn rules_name(rules: Rules) -> &'static str {
+    match rules {
+        Rules::Orthogonal => "orthogonal",
+        Rules::Standard => "standard",
+    }
+}
+
+fn termination_name(ending: Ending) -> &'static str {
+    match ending {
+        Ending::Completed => "normal",
+        Ending::Misplay(_) => "misplay",
+        Ending::Resignation(_) => "resignation",
+        Ending::DrawAgreement => "agreement",
+    }
+}
+
+fn result_string<const N: usize>(game: &Game<N>) -> String {
+    if !game.game_over() {
+        return "*".to_string();
+    }
+    match game.winner() {
+        Some(Player::White) => "1-0",
+        Some(Player::Black) => "0-1",
+        None => "1/2-1/2",
+    }.to_string()
+}
+
+// Today's date in UTC as YYYY.MM.DD
+fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400) as i64;
+    // Days since 1970-01-01 to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
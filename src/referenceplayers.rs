@@ -0,0 +1,212 @@
+use std::error::Error;
+use crate::game::{Game, Player, PlayerController, Pos};
+use crate::mctsplayer::MctsPlayer;
+use crate::rng::Rng;
+
+// Search depth of `AlphaBetaPlayer` when none is given to `reference_player`,
+// and of `Engine::AlphaBeta` when none is given to `Engine::from_name`
+pub(crate) const DEFAULT_DEPTH: usize = 4;
+
+// Score of a won game in `AlphaBetaPlayer`'s evaluation, per disc of margin
+pub(crate) const WIN_SCORE: i32 = 1000;
+
+/// Names of the reference players, as understood by `reference_player`.
+pub const REFERENCE_PLAYERS: [&str; 5] = ["random", "greedy", "positional", "alphabeta", "mcts"];
+
+/// Creates a reference player from its name in `REFERENCE_PLAYERS`, with an
+/// optional parameter after a colon: the seed for `random`, e.g. `random:7`,
+/// the depth for `alphabeta`, e.g. `alphabeta:6`, and the playouts per move
+/// for `mcts`, e.g. `mcts:5000`.
+pub fn reference_player<const N: usize>(spec: &str) -> Result<Box<dyn PlayerController<N>>, Box<dyn Error>> {
+    let (name, parameter) = match spec.split_once(':') {
+        Some((name, parameter)) => (name, Some(parameter)),
+        None => (spec, None),
+    };
+    let number = |default: u64| -> Result<u64, Box<dyn Error>> {
+        parameter.map_or(Ok(default), |p| p.parse().map_err(|err| format!("invalid parameter {:?} for {}: {}", p, name, err).into()))
+    };
+    Ok(match name {
+        "random" => Box::new(RandomPlayer::new(number(0)?)),
+        "greedy" if parameter.is_none() => Box::new(GreedyPlayer),
+        "positional" if parameter.is_none() => Box::new(PositionalPlayer),
+        "alphabeta" => match number(DEFAULT_DEPTH as u64)? {
+            0 => return Err("alphabeta depth must be at least 1".into()),
+            depth => Box::new(AlphaBetaPlayer::new(depth as usize)),
+        },
+        "mcts" if parameter.is_none() => Box::new(MctsPlayer::new(0)),
+        "mcts" => match number(0)? {
+            0 => return Err("mcts needs at least one playout".into()),
+            playouts => Box::new(MctsPlayer::new(0).playouts(u32::try_from(playouts)?)),
+        },
+        _ => return Err(format!("unknown player {:?}, expected one of {}", spec, REFERENCE_PLAYERS.join(", ")).into()),
+    })
+}
+
+/// Plays a uniformly random legal move.
+pub struct RandomPlayer {
+    rng: Rng,
+}
+
+impl RandomPlayer {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Rng::new(seed) }
+    }
+}
+
+impl<const N: usize> PlayerController<N> for RandomPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        let moves = legal_moves(game)?;
+        Ok(moves[self.rng.below(moves.len())])
+    }
+}
+
+/// Plays the move that flips the most tiles.
+pub struct GreedyPlayer;
+
+impl<const N: usize> PlayerController<N> for GreedyPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        best_move(game, |game, pos| game.flips(pos).len() as i32)
+    }
+}
+
+/// Plays the move leading to the best board by a table of square weights,
+/// valuing corners and edges and avoiding the squares next to corners.
+pub struct PositionalPlayer;
+
+impl<const N: usize> PlayerController<N> for PositionalPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        best_move(game, |game, pos| {
+            let player = game.current_player();
+            let mut after = game.clone();
+            after.play(pos);
+            positional_score(&after, player)
+        })
+    }
+}
+
+/// Searches `depth` moves ahead with negamax and alpha-beta pruning,
+/// evaluating positions by square weights and mobility, and finished games
+/// by the final disc count.
+pub struct AlphaBetaPlayer {
+    depth: usize,
+}
+
+impl AlphaBetaPlayer {
+    pub fn new(depth: usize) -> Self {
+        assert!(depth > 0, "depth must be at least 1");
+        Self { depth }
+    }
+}
+
+impl<const N: usize> PlayerController<N> for AlphaBetaPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        let moves = legal_moves(game)?;
+        let mut game = game.clone();
+        let mut alpha = -i32::MAX;
+        let mut best = moves[0];
+        for pos in moves {
+            game.play(pos);
+            let score = -negamax(&mut game, self.depth - 1, -i32::MAX, -alpha);
+            game.undo();
+            if score > alpha {
+                alpha = score;
+                best = pos;
+            }
+        }
+        Ok(best)
+    }
+}
+
+pub(crate) fn negamax<const N: usize>(game: &mut Game<N>, depth: usize, mut alpha: i32, beta: i32) -> i32 {
+    let player = game.current_player();
+    let moves = game.legal_moves(player);
+    if moves.is_empty() {
+        if game.legal_moves(player.flip()).is_empty() {
+            return WIN_SCORE * (game.count(player) as i32 - game.count(player.flip()) as i32);
+        }
+        if depth == 0 {
+            return evaluate(game);
+        }
+        game.skip();
+        let score = -negamax(game, depth - 1, -beta, -alpha);
+        game.undo();
+        return score;
+    }
+    if depth == 0 {
+        return evaluate(game);
+    }
+    for pos in moves {
+        game.play(pos);
+        let score = -negamax(game, depth - 1, -beta, -alpha);
+        game.undo();
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    alpha
+}
+
+// Square weights and mobility, for the player to move
+fn evaluate<const N: usize>(game: &Game<N>) -> i32 {
+    let player = game.current_player();
+    let mobility = game.legal_moves(player).len() as i32 - game.legal_moves(player.flip()).len() as i32;
+    positional_score(game, player) + 5 * mobility
+}
+
+// Sum of the weights of `player`'s squares minus the opponent's
+fn positional_score<const N: usize>(game: &Game<N>, player: Player) -> i32 {
+    let mut score = 0;
+    for x in 0..N {
+        for y in 0..N {
+            let pos = Pos::new(x, y);
+            let weight = square_weight(N, pos);
+            match game.tile(pos) {
+                Some(owner) if owner == player => score += weight,
+                Some(_) => score -= weight,
+                None => {},
+            }
+        }
+    }
+    score
+}
+
+// Corners can never be flipped, and the squares next to them give them away
+fn square_weight(n: usize, pos: Pos) -> i32 {
+    let edge = |c: usize| c == 0 || c == n - 1;
+    let next_to_edge = |c: usize| c == 1 || c == n - 2;
+    let (x, y) = (pos.x(), pos.y());
+    if edge(x) && edge(y) {
+        100
+    } else if next_to_edge(x) && next_to_edge(y) {
+        -50
+    } else if (edge(x) && next_to_edge(y)) || (next_to_edge(x) && edge(y)) {
+        -20
+    } else if edge(x) || edge(y) {
+        10
+    } else if next_to_edge(x) || next_to_edge(y) {
+        -5
+    } else {
+        1
+    }
+}
+
+fn legal_moves<const N: usize>(game: &Game<N>) -> Result<Vec<Pos>, Box<dyn Error>> {
+    let moves = game.legal_moves(game.current_player());
+    if moves.is_empty() {
+        return Err("no legal moves".into());
+    }
+    Ok(moves)
+}
+
+// The legal move with the highest score, the first one of those on ties
+fn best_move<const N: usize>(game: &Game<N>, score: impl Fn(&Game<N>, Pos) -> i32) -> Result<Pos, Box<dyn Error>> {
+    let mut best = None;
+    for pos in legal_moves(game)? {
+        let pos_score = score(game, pos);
+        if best.is_none_or(|(best_score, _)| pos_score > best_score) {
+            best = Some((pos_score, pos));
+        }
+    }
+    Ok(best.expect("there are legal moves").1)
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+use crate::game::{Game, PlayerController, Pos};
+
+/// Plays its side of a recorded game, e.g. `MatchRecord::moves` or
+/// `Game::move_list`, to reproduce a game against a live opponent. Before
+/// every move it checks that the game so far follows the recording, and
+/// fails with the first move that doesn't. The game has to be played from
+/// the initial position, since positions set up from a string have no moves.
+pub struct ReplayPlayer {
+    moves: Vec<u8>,
+}
+
+impl ReplayPlayer {
+    /// `moves` holds the moves of both players as offsets, passes left out.
+    pub fn new(moves: Vec<u8>) -> Self {
+        Self { moves }
+    }
+}
+
+impl<const N: usize> PlayerController<N> for ReplayPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        let played = game.move_list();
+        let square = |offset: u8| Pos::from_offset(offset, N).to_string();
+        if let Some(index) = played.iter().zip(&self.moves).position(|(played, recorded)| played != recorded) {
+            return Err(format!("the game left the recording at move {}: recorded {}, played {}",
+                               index + 1, square(self.moves[index]), square(played[index])).into());
+        }
+        if played.len() > self.moves.len() {
+            return Err(format!("the game went on after the recording ended at move {}, with {}",
+                               self.moves.len(), square(played[self.moves.len()])).into());
+        }
+        let &offset = self.moves.get(played.len())
+            .ok_or_else(|| format!("the recording ends after {} moves, but the game goes on", self.moves.len()))?;
+        let pos = Pos::from_offset(offset, N);
+        if game.flips(pos).is_empty() {
+            return Err(format!("recorded move {} ({}) is illegal here, the recording is for another board or rules",
+                               played.len() + 1, pos).into());
+        }
+        Ok(pos)
+    }
+}
@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::game::{Game, PlayerController, Pos, Turn};
+use crate::textprotocol::TextProtocol;
+
+// How long connecting, accepting a connection and the handshake may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Wait before the first reconnection attempt, doubled for every further one
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+// How the connection to the bot is made, and made again after it drops
+enum Endpoint {
+    Connect(Vec<SocketAddr>),
+    Accept(TcpListener),
+}
+
+/// A bot running as a server, or connecting as a client, speaking the same
+/// line protocol as `ProcessPlayer` over TCP. If the connection drops, the
+/// player reconnects and asks again, up to a few times per request.
+pub struct SocketPlayer {
+    endpoint: Endpoint,
+    connection: Option<(TcpStream, TextProtocol)>,
+    timeout: Duration,
+    reconnects: u32,
+}
+
+impl SocketPlayer {
+    /// Connects to a bot listening on `addr`.
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, Box<dyn Error>> {
+        let addrs = addr.to_socket_addrs()?.collect();
+        Self::new(Endpoint::Connect(addrs))
+    }
+
+    /// Waits for a bot to connect to `listener`. On reconnection, waits for
+    /// it to connect again.
+    pub fn accept(listener: TcpListener) -> Result<Self, Box<dyn Error>> {
+        Self::new(Endpoint::Accept(listener))
+    }
+
+    fn new(endpoint: Endpoint) -> Result<Self, Box<dyn Error>> {
+        let mut player = Self { endpoint, connection: None, timeout: Duration::from_secs(10), reconnects: 3 };
+        player.open()?;
+        Ok(player)
+    }
+
+    /// How long the bot gets to answer each request, 10 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many times to reconnect for a request after the connection drops,
+    /// 3 by default.
+    pub fn reconnects(mut self, reconnects: u32) -> Self {
+        self.reconnects = reconnects;
+        self
+    }
+
+    /// The name the bot gave in the last handshake, possibly empty.
+    pub fn name(&self) -> &str {
+        self.connection.as_ref().map_or("", |(_, protocol)| protocol.name())
+    }
+
+    fn open(&mut self) -> Result<(), Box<dyn Error>> {
+        self.close();
+        let stream = match &self.endpoint {
+            Endpoint::Connect(addrs) => connect(addrs)?,
+            Endpoint::Accept(listener) => accept(listener)?,
+        };
+        let protocol = TextProtocol::connect(stream.try_clone()?, stream.try_clone()?, CONNECT_TIMEOUT)?;
+        self.connection = Some((stream, protocol));
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some((stream, mut protocol)) = self.connection.take() {
+            protocol.quit();
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    // Makes a request, reconnecting and trying again if the connection drops.
+    // Bad answers and timeouts fail right away, and a timed out connection is
+    // replaced before the next request.
+    fn request<T>(&mut self, mut request: impl FnMut(&mut TextProtocol) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        let mut error: Box<dyn Error> = "not connected".into();
+        let mut delay = RECONNECT_DELAY;
+        for reconnect in 0..=self.reconnects {
+            if reconnect > 0 {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            if self.connection.as_ref().is_none_or(|(_, protocol)| protocol.closed()) {
+                if let Err(err) = self.open() {
+                    error = err;
+                    continue;
+                }
+            }
+            let (_, protocol) = self.connection.as_mut().expect("connected above");
+            match request(protocol) {
+                Err(err) if protocol.closed() && !protocol.timed_out() => error = err,
+                result => return result,
+            }
+        }
+        Err(format!("connection lost after {} reconnects: {}", self.reconnects, error).into())
+    }
+}
+
+impl<const N: usize> PlayerController<N> for SocketPlayer {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        match self.take_turn(game)? {
+            Turn::Move(pos) => Ok(pos),
+            turn => Err(format!("expected a move, got {:?}", turn).into()),
+        }
+    }
+
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
+        let timeout = self.timeout;
+        self.request(|protocol| protocol.take_turn(game, timeout))
+    }
+
+    fn accept_draw(&mut self, game: &Game<N>) -> bool {
+        let timeout = self.timeout;
+        self.request(|protocol| protocol.accept_draw(game, timeout)).unwrap_or(false)
+    }
+}
+
+impl Drop for SocketPlayer {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn connect(addrs: &[SocketAddr]) -> Result<TcpStream, Box<dyn Error>> {
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(match last_err {
+        Some(err) => format!("could not connect: {}", err).into(),
+        None => "no address to connect to".into(),
+    })
+}
+
+// `TcpListener::accept` can't time out, so poll it
+fn accept(listener: &TcpListener) -> Result<TcpStream, Box<dyn Error>> {
+    listener.set_nonblocking(true)?;
+    let start = Instant::now();
+    let accepted: Result<TcpStream, Box<dyn Error>> = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break Ok(stream),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock && start.elapsed() < CONNECT_TIMEOUT => {
+                thread::sleep(Duration::from_millis(10));
+            },
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                break Err(format!("no connection within {:?}", CONNECT_TIMEOUT).into());
+            },
+            Err(err) => break Err(err.into()),
+        }
+    };
+    listener.set_nonblocking(false)?;
+    let stream = accepted?;
+    stream.set_nonblocking(false)?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Ending;
+    use crate::matchrunner::{MatchConfig, MatchRunner};
+    use crate::referenceplayers::{AlphaBetaPlayer, RandomPlayer};
+    use crate::textprotocol::serve_player;
+
+    #[test]
+    fn plays_a_game_against_a_served_player() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut player = AlphaBetaPlayer::new(2);
+            serve_player::<8>(stream.try_clone().unwrap(), stream, "alphabeta", &mut player)
+                .map_err(|err| err.to_string())
+        });
+
+        let mut white = SocketPlayer::connect(addr).unwrap();
+        assert_eq!(white.name(), "alphabeta");
+        let mut black = RandomPlayer::new(1);
+        let record = MatchRunner::<8>::new(MatchConfig::default()).unwrap().run(&mut white, &mut black);
+        drop(white);
+
+        assert_eq!(record.misplays, Vec::new());
+        assert_eq!(record.ending, Ending::Completed);
+        server.join().unwrap().unwrap();
+    }
+}
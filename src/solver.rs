@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::error::Error;
+use crate::game::{Game, Player, Pos};
+
+// The transposition table is cleared when it grows past this many entries
+const MAX_TABLE_ENTRIES: usize = 1 << 22;
+
+/// The result of perfect play from a position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// Final disc count of the player to move minus the opponent's.
+    pub score: i32,
+    /// A move reaching `score`, `None` if the player to move has to pass or
+    /// the game is over.
+    pub best_move: Option<Pos>,
+    /// Positions searched.
+    pub nodes: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    score: i32,
+    bound: Bound,
+    best_move: Option<Pos>,
+}
+
+/// Exact endgame solver: negamax with alpha-beta pruning, searching moves
+/// that leave the opponent the fewest replies first, with a transposition
+/// table keyed by the Zobrist hash.
+pub struct Solver {
+    max_empties: usize,
+    table: HashMap<u64, Entry>,
+    nodes: u64,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            max_empties: 14,
+            table: HashMap::new(),
+            nodes: 0,
+        }
+    }
+
+    /// Refuse positions with more empty spaces than this, default 14. Each
+    /// extra empty space makes solving several times slower.
+    pub fn max_empties(mut self, max_empties: usize) -> Self {
+        self.max_empties = max_empties;
+        self
+    }
+
+    /// Solves the position for the player to move. Games that ended by
+    /// misplay, resignation or agreement can't be solved.
+    pub fn solve<const N: usize>(&mut self, game: &Game<N>) -> Result<Solution, Box<dyn Error>> {
+        if let Some(ending) = game.ending() {
+            return Err(format!("the game is over: {}", ending).into());
+        }
+        let empties = N * N - game.count(Player::White) - game.count(Player::Black);
+        if empties > self.max_empties {
+            return Err(format!("{} empty spaces, can solve at most {}", empties, self.max_empties).into());
+        }
+
+        // Table entries depend on the rules, which the hash doesn't include
+        self.table.clear();
+        self.nodes = 0;
+        let mut game = game.clone();
+        let max_score = (N * N) as i32;
+        let score = self.negamax(&mut game, -max_score - 1, max_score + 1);
+        let best_move = self.table.get(&game.zobrist_hash()).and_then(|entry| entry.best_move);
+        Ok(Solution { score, best_move, nodes: self.nodes })
+    }
+
+    fn negamax<const N: usize>(&mut self, game: &mut Game<N>, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        let player = game.current_player();
+        let moves = game.legal_moves(player);
+        if moves.is_empty() {
+            if game.legal_moves(player.flip()).is_empty() {
+                return game.count(player) as i32 - game.count(player.flip()) as i32;
+            }
+            game.skip();
+            let score = -self.negamax(game, -beta, -alpha);
+            game.undo();
+            return score;
+        }
+
+        let hash = game.zobrist_hash();
+        let original_alpha = alpha;
+        let mut hash_move = None;
+        if let Some(entry) = self.table.get(&hash) {
+            match entry.bound {
+                Bound::Exact => return entry.score,
+                Bound::Lower => alpha = alpha.max(entry.score),
+                Bound::Upper => beta = beta.min(entry.score),
+            }
+            if alpha >= beta {
+                return entry.score;
+            }
+            hash_move = entry.best_move;
+        }
+
+        let mut best_score = i32::MIN;
+        let mut best_move = None;
+        for pos in self.ordered_moves(game, moves, hash_move) {
+            game.play(pos);
+            let score = -self.negamax(game, -beta, -alpha);
+            game.undo();
+            if score > best_score {
+                best_score = score;
+                best_move = Some(pos);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        if self.table.len() >= MAX_TABLE_ENTRIES {
+            self.table.clear();
+        }
+        self.table.insert(hash, Entry { score: best_score, bound, best_move });
+        best_score
+    }
+
+    // The move from the table first, then by how many moves the opponent has
+    // after each, fewest first
+    fn ordered_moves<const N: usize>(&self, game: &mut Game<N>, moves: Vec<Pos>, hash_move: Option<Pos>) -> Vec<Pos> {
+        let opponent = game.current_player().flip();
+        let mut ordered: Vec<(usize, Pos)> = moves.into_iter()
+            .map(|pos| {
+                if Some(pos) == hash_move {
+                    return (0, pos);
+                }
+                game.play(pos);
+                let mobility = game.legal_moves(opponent).len();
+                game.undo();
+                (mobility + 1, pos)
+            })
+            .collect();
+        ordered.sort_by_key(|&(key, _)| key);
+        ordered.into_iter().map(|(_, pos)| pos).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve<const N: usize>(position: &str) -> Solution {
+        let game = Game::<N>::from_position_str(position).unwrap();
+        let solution = Solver::new().solve(&game).unwrap();
+        assert_reaches_score(&game, &solution);
+        solution
+    }
+
+    // Plays the best move, or passes, and checks that the position after it
+    // is worth the same to the player who was to move
+    fn assert_reaches_score<const N: usize>(game: &Game<N>, solution: &Solution) {
+        let player = game.current_player();
+        let mut after = game.clone();
+        match solution.best_move {
+            Some(pos) => after.play(pos),
+            None => after.skip(),
+        }
+        let score = if after.game_over() {
+            after.count(player) as i32 - after.count(player.flip()) as i32
+        } else {
+            let score = Solver::new().solve(&after).unwrap().score;
+            if after.current_player() == player { score } else { -score }
+        };
+        assert_eq!(score, solution.score, "{:?} doesn't reach the score", solution.best_move);
+    }
+
+    #[test]
+    fn single_move_fills_the_board() {
+        let solution = solve::<4>("4:WWWW/WWWW/WWWW/WWB. W");
+        assert_eq!(solution.score, 16);
+        assert_eq!(solution.best_move, Some(Pos::new(3, 3)));
+    }
+
+    #[test]
+    fn player_to_move_passes() {
+        let solution = solve::<4>("4:WWWW/WWWW/WWWW/WWB. B");
+        assert_eq!(solution.score, -16);
+        assert_eq!(solution.best_move, None);
+    }
+
+    #[test]
+    fn game_ends_before_the_board_fills() {
+        // Taking the only black disc leaves neither player a move
+        let solution = solve::<4>("4:WB../..../..../.... W");
+        assert_eq!(solution.score, 3);
+        assert_eq!(solution.best_move, Some(Pos::new(2, 0)));
+    }
+
+    #[test]
+    fn endgame_with_orthogonal_rules() {
+        let solution = solve::<8>("8:.BBBBBB.WBWWWWWW.BBBBBBWBBBBBBBWBWBBBBBWBWWWWWWW.WWW.WWW..WW.W.. B");
+        assert_eq!(solution.score, -4);
+        // e7 and g8 are equally good
+        assert!([Some(Pos::new(4, 6)), Some(Pos::new(6, 7))].contains(&solution.best_move));
+    }
+
+    #[test]
+    fn endgame_with_standard_rules() {
+        let solution = solve::<8>("8:BW.BB.W.BBBBBBB.BBBBWBB.BBWBWWWW.BBBBWWWWBBBBWWW.BBBWW..BBBBWWB. B standard");
+        assert_eq!(solution.score, 6);
+        assert_eq!(solution.best_move, Some(Pos::new(2, 0)));
+    }
+
+    #[test]
+    fn refuses_too_many_empty_spaces() {
+        let game = Game::<8>::from_position_str("8:...........................WB......BW........................... B").unwrap();
+        assert!(Solver::new().solve(&game).is_err());
+    }
+}
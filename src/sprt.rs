@@ -0,0 +1,286 @@
+use std::error::Error;
+use std::fmt;
+use crate::game::Player;
+use crate::matchrunner::MatchRecord;
+use crate::opening::OpeningSuite;
+use crate::tournament::{play_pairing, Pairing, PlayerFactory, TournamentSettings};
+
+// Entrant indices passed to the player factory
+const CANDIDATE: usize = 0;
+const BASELINE: usize = 1;
+
+// Two-sided 95% confidence
+const CONFIDENCE_Z: f64 = 1.96;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SprtDecision {
+    /// The candidate gains at least `elo1` over the baseline.
+    Accept,
+    /// The candidate gains at most `elo0` over the baseline.
+    Reject,
+    /// Stopped at the game limit before either bound was reached.
+    Inconclusive,
+}
+
+/// Sequential probability ratio test between a candidate and a baseline
+/// version of a bot. Pairs of games, one with each colour from the same
+/// opening, are played until the log-likelihood ratio of "the candidate is
+/// `elo1` stronger" over "the candidate is `elo0` stronger" crosses one of the
+/// bounds given by the error rates `alpha` and `beta`. Its threads play
+/// whole pairs, which are evaluated in order, so the outcome doesn't depend
+/// on the number of threads.
+#[derive(Debug, Clone)]
+pub struct Sprt {
+    settings: TournamentSettings,
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+    openings: OpeningSuite,
+    max_games: usize,
+}
+
+impl Sprt {
+    /// The two entrants are the candidate and then the baseline.
+    pub fn new(settings: TournamentSettings, elo0: f64, elo1: f64) -> Self {
+        assert_eq!(settings.entrants.len(), 2, "expected a candidate and a baseline");
+        assert!(elo0 < elo1);
+        Self {
+            settings, elo0, elo1, alpha: 0.05, beta: 0.05,
+            openings: OpeningSuite::default(), max_games: 20000,
+        }
+    }
+
+    /// Chance of accepting when the candidate is only `elo0` stronger.
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Chance of rejecting when the candidate is `elo1` stronger.
+    pub fn beta(mut self, beta: f64) -> Self {
+        self.beta = beta;
+        self
+    }
+
+    /// Openings the pairs are played from, in turn. Without openings every
+    /// pair starts from the initial position.
+    pub fn openings(mut self, openings: OpeningSuite) -> Self {
+        self.openings = openings;
+        self
+    }
+
+    /// Stops the test without a decision after this many games.
+    pub fn max_games(mut self, max_games: usize) -> Self {
+        self.max_games = max_games.max(2);
+        self
+    }
+
+    pub fn settings(&self) -> &TournamentSettings {
+        &self.settings
+    }
+
+    /// Lower and upper bound of the log-likelihood ratio.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// Log-likelihood ratio of the results, from the candidate's point of
+    /// view, using the normal approximation of the score distribution.
+    pub fn llr(&self, wins: u32, draws: u32, losses: u32) -> f64 {
+        let games = (wins + draws + losses) as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        let score = (wins as f64 + draws as f64 / 2.0) / games;
+        let variance = score_variance(wins, draws, losses);
+        games * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance)
+    }
+
+    fn decide(&self, llr: f64) -> Option<SprtDecision> {
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtDecision::Accept)
+        } else if llr <= lower {
+            Some(SprtDecision::Reject)
+        } else {
+            None
+        }
+    }
+
+    /// Runs the test. `new_player` is called with 0 for the candidate and 1
+    /// for the baseline.
+    pub fn run<const N: usize>(&self, new_player: &PlayerFactory<N>) -> Result<SprtReport, Box<dyn Error>> {
+        let mut report = SprtReport {
+            games: Vec::new(), wins: 0, draws: 0, losses: 0, llr: 0.0,
+            bounds: self.bounds(), decision: SprtDecision::Inconclusive,
+        };
+        let max_pairs = self.max_games / 2;
+        let mut next_pair = 0;
+        while next_pair < max_pairs {
+            let batch: Vec<usize> = (next_pair..max_pairs.min(next_pair + self.settings.threads)).collect();
+            next_pair += batch.len();
+            let pairs = self.settings.executor().run(&batch, |&pair| self.play_pair(pair, new_player))
+                .into_iter().collect::<Result<Vec<_>, _>>()?;
+            for games in pairs {
+                for (pairing, record) in games {
+                    let candidate = if pairing.white == CANDIDATE { Player::White } else { Player::Black };
+                    match record.winner {
+                        None => report.draws += 1,
+                        Some(winner) if winner == candidate => report.wins += 1,
+                        Some(_) => report.losses += 1,
+                    }
+                    report.games.push((pairing, record));
+                }
+                report.llr = self.llr(report.wins, report.draws, report.losses);
+                if let Some(decision) = self.decide(report.llr) {
+                    report.decision = decision;
+                    return Ok(report);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn play_pair<const N: usize>(&self, pair: usize, new_player: &PlayerFactory<N>) -> Result<Vec<(Pairing, MatchRecord)>, String> {
+        let opening = match self.openings.openings() {
+            [] => None,
+            openings => Some(openings[pair % openings.len()].clone()),
+        };
+        [(CANDIDATE, BASELINE), (BASELINE, CANDIDATE)].into_iter().enumerate().map(|(i, (white, black))| {
+            let pairing = Pairing { game: pair * 2 + i, round: pair, white, black, opening: opening.clone() };
+            let record = play_pairing(self.settings.match_config(&pairing), &pairing, new_player)?;
+            Ok((pairing, record))
+        }).collect()
+    }
+}
+
+/// Outcome of an SPRT run, counted from the candidate's point of view.
+#[derive(Debug, Clone)]
+pub struct SprtReport {
+    /// Entrant 0 is the candidate, 1 the baseline.
+    pub games: Vec<(Pairing, MatchRecord)>,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub llr: f64,
+    pub bounds: (f64, f64),
+    pub decision: SprtDecision,
+}
+
+impl SprtReport {
+    /// Average points per game, between 0 and 1.
+    pub fn score(&self) -> f64 {
+        let games = self.wins + self.draws + self.losses;
+        if games == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / games as f64
+    }
+
+    /// Elo difference that corresponds to the score.
+    pub fn elo(&self) -> f64 {
+        elo_difference(self.score())
+    }
+
+    /// 95% confidence interval of the Elo difference.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let games = (self.wins + self.draws + self.losses).max(1) as f64;
+        let error = CONFIDENCE_Z * (score_variance(self.wins, self.draws, self.losses) / games).sqrt();
+        (elo_difference(self.score() - error), elo_difference(self.score() + error))
+    }
+}
+
+impl fmt::Display for SprtReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (low, high) = self.confidence_interval();
+        writeln!(f, "games: {} (+{} ={} -{})", self.games.len(), self.wins, self.draws, self.losses)?;
+        writeln!(f, "score: {:.1}%", self.score() * 100.0)?;
+        writeln!(f, "elo: {:+.1} [{:+.1}, {:+.1}]", self.elo(), low, high)?;
+        writeln!(f, "llr: {:.2} [{:.2}, {:.2}]", self.llr, self.bounds.0, self.bounds.1)?;
+        let decision = match self.decision {
+            SprtDecision::Accept => "accepted",
+            SprtDecision::Reject => "rejected",
+            SprtDecision::Inconclusive => "inconclusive",
+        };
+        writeln!(f, "result: {}", decision)
+    }
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Elo difference implied by an average score per game. Scores of 0 and 1
+/// are clamped, so that they give about -1200 and +1200 instead of infinity.
+pub fn elo_difference(score: f64) -> f64 {
+    let score = score.clamp(0.001, 0.999);
+    // Written so that a score of 0.5 gives 0 rather than -0
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+// Variance of the score of a single game. Half a game of each outcome is
+// added so that a one-sided start doesn't give a variance of 0.
+fn score_variance(wins: u32, draws: u32, losses: u32) -> f64 {
+    let (wins, draws, losses) = (wins as f64 + 0.5, draws as f64 + 0.5, losses as f64 + 0.5);
+    let games = wins + draws + losses;
+    let score = (wins + draws / 2.0) / games;
+    (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2)) / games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprt(elo0: f64, elo1: f64) -> Sprt {
+        Sprt::new(TournamentSettings::new(vec!["candidate".to_string(), "baseline".to_string()]), elo0, elo1)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn bounds_follow_from_the_error_rates() {
+        let (lower, upper) = sprt(0.0, 10.0).bounds();
+        assert_close(lower, -2.944439);
+        assert_close(upper, 2.944439);
+        let (lower, upper) = sprt(0.0, 10.0).alpha(0.01).beta(0.1).bounds();
+        assert_close(lower, -2.292535);
+        assert_close(upper, 4.499810);
+    }
+
+    #[test]
+    fn llr_of_known_results() {
+        let test = sprt(0.0, 10.0);
+        assert_eq!(test.llr(0, 0, 0), 0.0);
+        assert_close(test.llr(60, 20, 40), 0.653846);
+        assert_close(test.llr(40, 20, 60), -0.777395);
+        // An even score is closer to elo0 than to elo1
+        assert_close(test.llr(500, 0, 500), -0.414189);
+    }
+
+    #[test]
+    fn accepts_a_stronger_candidate() {
+        let test = sprt(0.0, 10.0);
+        // A 55% score is about 35 Elo, but takes a while to tell from 10
+        assert_eq!(test.decide(test.llr(220, 0, 180)), None);
+        assert_eq!(test.decide(test.llr(880, 0, 720)), Some(SprtDecision::Accept));
+    }
+
+    #[test]
+    fn rejects_a_candidate_that_is_no_stronger() {
+        let test = sprt(0.0, 10.0);
+        assert_eq!(test.decide(test.llr(180, 0, 220)), None);
+        assert_eq!(test.decide(test.llr(720, 0, 880)), Some(SprtDecision::Reject));
+    }
+
+    #[test]
+    fn elo_difference_of_scores() {
+        assert!(elo_difference(0.5) == 0.0 && elo_difference(0.5).is_sign_positive());
+        assert_close(elo_difference(0.75), 190.848501);
+        assert_close(elo_difference(1.0), 1199.826195);
+        assert_close(elo_difference(0.0), -1199.826195);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::error::Error;
+use crate::game::Player;
+use crate::matchrunner::MatchRecord;
+use crate::tournament::{play_pairing, Pairing, PlayerFactory, Standings, TournamentResult, TournamentSettings};
+
+// Pairings the search for a round without rematches may try before settling
+// for `pair_greedily`. The search grows factorially with the number of
+// entrants when no such round exists.
+const PAIRING_SEARCH_STEPS: usize = 100_000;
+
+/// Swiss-system tournament: a fixed number of rounds in which entrants play
+/// opponents with a similar score that they haven't met yet.
+#[derive(Debug, Clone)]
+pub struct Swiss {
+    settings: TournamentSettings,
+    rounds: usize,
+}
+
+impl Swiss {
+    /// The entrants are in seeding order, strongest first.
+    pub fn new(settings: TournamentSettings, rounds: usize) -> Self {
+        Self { settings, rounds }
+    }
+
+    pub fn settings(&self) -> &TournamentSettings {
+        &self.settings
+    }
+
+    /// Pairs `round` given the games played and byes awarded so far. Entrants
+    /// are ranked by score, and each is paired with the highest ranked
+    /// opponent it hasn't played yet, so pairings stay within score groups
+    /// when possible. Rematches only happen when no other way to pair
+    /// everyone is found, and are then kept few. With an odd number of entrants the lowest ranked
+    /// entrant that hasn't had a bye sits out. Returns the pairings and the bye.
+    pub fn pair_round(&self, round: usize, games: &[(Pairing, MatchRecord)], byes: &[usize]) -> (Vec<Pairing>, Option<usize>) {
+        let standings = Standings::new(&self.settings.entrants, games, byes);
+        // Order by score only, seeding breaks ties
+        let mut order: Vec<(f64, usize)> = standings.0.iter().map(|s| (s.points, s.entrant)).collect();
+        order.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        let mut order: Vec<usize> = order.into_iter().map(|(_, entrant)| entrant).collect();
+
+        let bye = if order.len() % 2 == 1 {
+            let i = order.iter().rposition(|e| !byes.contains(e)).unwrap_or(order.len() - 1);
+            Some(order.remove(i))
+        } else {
+            None
+        };
+
+        let played: HashSet<(usize, usize)> = games.iter()
+            .map(|(p, _)| (p.white.min(p.black), p.white.max(p.black)))
+            .collect();
+        let mut steps = PAIRING_SEARCH_STEPS;
+        let pairs = pair_up(&order, &played, &mut steps)
+            .unwrap_or_else(|| pair_greedily(&order, &played));
+
+        let first_game = games.iter().map(|(p, _)| p.game + 1).max().unwrap_or(0);
+        let pairings = pairs.into_iter().enumerate().map(|(i, (a, b))| {
+            let (white, black) = colours(a, b, games);
+            Pairing { game: first_game + i, round, white, black, opening: None }
+        }).collect();
+        (pairings, bye)
+    }
+
+    pub fn run<const N: usize>(&self, new_player: &PlayerFactory<N>) -> Result<TournamentResult, Box<dyn Error>> {
+        let mut games: Vec<(Pairing, MatchRecord)> = Vec::new();
+        let mut byes = Vec::new();
+        for round in 0..self.rounds {
+            let bye_entrants: Vec<usize> = byes.iter().map(|&(_, e)| e).collect();
+            let (pairings, bye) = self.pair_round(round, &games, &bye_entrants);
+            let records = self.settings.executor().run(&pairings, |pairing| {
+                play_pairing(self.settings.match_config(pairing), pairing, new_player)
+            }).into_iter().collect::<Result<Vec<_>, _>>()?;
+            games.extend(pairings.into_iter().zip(records));
+            byes.extend(bye.map(|entrant| (round, entrant)));
+        }
+        let bye_entrants: Vec<usize> = byes.iter().map(|&(_, e)| e).collect();
+        let standings = Standings::new(&self.settings.entrants, &games, &bye_entrants);
+        Ok(TournamentResult { games, byes, standings })
+    }
+}
+
+// Pairs the first entrant with the first one after it that it hasn't
+// played, backtracking if the rest can't be paired without rematches. Gives
+// up when `steps` run out.
+fn pair_up(order: &[usize], played: &HashSet<(usize, usize)>, steps: &mut usize) -> Option<Vec<(usize, usize)>> {
+    let Some((&first, rest)) = order.split_first() else {
+        return Some(Vec::new());
+    };
+    if *steps == 0 {
+        return None;
+    }
+    *steps -= 1;
+    for (i, &other) in rest.iter().enumerate() {
+        if played.contains(&(first.min(other), first.max(other))) {
+            continue;
+        }
+        let remaining: Vec<usize> = rest.iter().enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, &e)| e)
+            .collect();
+        if let Some(mut pairs) = pair_up(&remaining, played, steps) {
+            pairs.insert(0, (first, other));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+// Pairs without backtracking. The entrant with the fewest opponents left that
+// it hasn't played goes first, so that those few aren't taken by others, and
+// gets the highest ranked of them, or of everyone if it has played them all.
+fn pair_greedily(order: &[usize], played: &HashSet<(usize, usize)>) -> Vec<(usize, usize)> {
+    let met = |a: usize, b: usize| played.contains(&(a.min(b), a.max(b)));
+    let mut remaining = order.to_vec();
+    let mut pairs = Vec::new();
+    while remaining.len() >= 2 {
+        // Entrants that have played everyone left rematch anyway, so they go last
+        let options = |entrant: usize| match remaining.iter().filter(|&&other| other != entrant && !met(entrant, other)).count() {
+            0 => usize::MAX,
+            count => count,
+        };
+        let (i, _) = remaining.iter().enumerate()
+            .min_by_key(|&(i, &entrant)| (options(entrant), i))
+            .expect("at least two entrants remain");
+        let first = remaining.remove(i);
+        let j = remaining.iter().position(|&other| !met(first, other)).unwrap_or(0);
+        pairs.push((first, remaining.remove(j)));
+    }
+    pairs
+}
+
+// White goes to whoever has played white less often, then to whoever
+// played black most recently, then to the higher ranked entrant `a`.
+fn colours(a: usize, b: usize, games: &[(Pairing, MatchRecord)]) -> (usize, usize) {
+    let history = |entrant: usize| games.iter().filter_map(move |(p, _)| {
+        if p.white == entrant {
+            Some(Player::White)
+        } else if p.black == entrant {
+            Some(Player::Black)
+        } else {
+            None
+        }
+    });
+    let balance = |entrant: usize| history(entrant)
+        .map(|colour| if colour == Player::White { 1 } else { -1 })
+        .sum::<i32>();
+    let (balance_a, balance_b) = (balance(a), balance(b));
+    if balance_a != balance_b {
+        return if balance_a < balance_b { (a, b) } else { (b, a) };
+    }
+    match (history(a).next_back(), history(b).next_back()) {
+        (Some(Player::White), Some(Player::Black)) => (b, a),
+        _ => (a, b),
+    }
+}
@@ -1,35 +1,73 @@
 use std::error::Error;
+use wasmthello::Engine;
 use wasmthello::PlayerController;
 use wasmthello::Pos;
 use wasmthello::Game;
-use wasmthello::Player;
+use wasmthello::Turn;
 use std::io;
 
 pub struct TerminalPlayer<const N: usize> {
+    // Scores the moves listed by `hint`
+    engine: Engine,
+}
+
+impl<const N: usize> TerminalPlayer<N> {
+    pub fn new(engine: Engine) -> Self {
+        Self { engine }
+    }
 }
 
 impl<const N: usize> PlayerController<N> for TerminalPlayer<N> {
 
     fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
-        if game.current_player() == Player::White {
-            println!("White (1) player's turn");
-        } else {
-            println!("Black (2) player's turn");
+        match self.take_turn(game)? {
+            Turn::Move(pos) => Ok(pos),
+            turn => Err(format!("expected a move, got {:?}", turn).into()),
         }
-        game.print();
+    }
 
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
         let legal_moves = game.legal_moves(game.current_player());
-        let legal_move_count = legal_moves.len();
-        assert!(legal_move_count > 0);
-        for pos in legal_moves.into_iter() {
-            let offset = pos.to_offset(N);
-            println!("legal move {}: {:?}", offset, pos);
+        assert!(!legal_moves.is_empty());
+        let names: Vec<String> = legal_moves.iter().map(|pos| pos.to_string()).collect();
+        println!("legal moves: {}", names.join(" "));
+        println!("or 'resign', 'draw' to offer a draw, 'undo' to take back your last move, or 'hint'");
+        loop {
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 {
+                return Err("end of input".into());
+            }
+            match input.trim() {
+                "resign" => return Ok(Turn::Resign),
+                "draw" => return Ok(Turn::OfferDraw),
+                "undo" => return Ok(Turn::Undo),
+                "hint" => print_hints(game, self.engine),
+                // Typos ask again rather than forfeit the game
+                input => match input.parse::<Pos>() {
+                    Ok(pos) if legal_moves.contains(&pos) => {
+                        println!("placing {}", pos);
+                        return Ok(Turn::Move(pos));
+                    },
+                    Ok(pos) if pos.x() >= N || pos.y() >= N => println!("{} is off the board, try again", pos),
+                    Ok(pos) => println!("{} is not a legal move, try again", pos),
+                    Err(err) => println!("{}, try again", err),
+                },
+            }
         }
+    }
+
+    fn accept_draw(&mut self, _game: &Game<N>) -> bool {
+        println!("Your opponent offers a draw, accept? (y/n)");
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let ans: i32 = input.trim().parse()?;
-        let pos = Pos::from_offset(ans as u8, N);
-        println!("placing {:?}", pos);
-        Ok(pos)
+        io::stdin().read_line(&mut input).is_ok() && input.trim().eq_ignore_ascii_case("y")
+    }
+}
+
+/// Lists the legal moves in `game`, best first by `engine`, with the discs
+/// each flips and the moves it leaves the opponent.
+pub fn print_hints<const N: usize>(game: &Game<N>, engine: Engine) {
+    println!("move   score  flips  opponent moves");
+    for evaluation in engine.evaluate_moves(game) {
+        println!("{:<4} {:>+7}  {:>5}  {:>14}", evaluation.pos.to_string(), evaluation.score, evaluation.flips, evaluation.opponent_mobility);
     }
 }
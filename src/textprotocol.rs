@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use crate::game::{Game, PlayerController, Turn};
+
+/// Version of the line protocol spoken by `ProcessPlayer` and `SocketPlayer`,
+/// sent in the handshake.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// One side of a connection to a bot speaking the line protocol. Lines are
+// read on a thread of their own, so that waiting for one can time out.
+pub(crate) struct TextProtocol {
+    lines: Receiver<io::Result<String>>,
+    writer: Box<dyn Write + Send>,
+    // Name the bot gave in the handshake
+    name: String,
+    // Set once reading or writing fails or an answer times out, after which
+    // every request fails. Answers aren't tied to requests, so after a
+    // timeout the late answer could be taken for the answer to the next one.
+    closed: bool,
+    // Set when the connection was closed by a timeout
+    timed_out: bool,
+}
+
+impl TextProtocol {
+    /// Connects over `reader` and `writer`, and waits up to `timeout` for
+    /// the bot to answer the handshake.
+    pub(crate) fn connect(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static, timeout: Duration) -> Result<Self, Box<dyn Error>> {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let failed = line.is_err();
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let mut protocol = Self { lines, writer: Box::new(writer), name: String::new(), closed: false, timed_out: false };
+        protocol.send(&format!("wasmthello {}", PROTOCOL_VERSION))?;
+        let answer = protocol.receive(timeout).map_err(|err| format!("handshake failed: {}", err))?;
+        match answer.split_once(' ').unwrap_or((&answer, "")) {
+            ("ready", name) => protocol.name = name.trim().to_string(),
+            _ => return Err(format!("handshake failed: expected \"ready\", got {:?}", answer).into()),
+        }
+        Ok(protocol)
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the connection can't be used anymore, because it failed or
+    /// the bot didn't answer in time.
+    pub(crate) fn closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Whether the connection was closed because the bot didn't answer in time.
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    pub(crate) fn take_turn<const N: usize>(&mut self, game: &Game<N>, timeout: Duration) -> Result<Turn, Box<dyn Error>> {
+        self.send(&format!("turn {}", game.to_position_str()))?;
+        let answer = self.receive(timeout)?;
+        match answer.split_once(' ').unwrap_or((&answer, "")) {
+            ("play", pos) => Ok(Turn::Move(pos.parse()?)),
+            ("resign", "") => Ok(Turn::Resign),
+            ("draw", "") => Ok(Turn::OfferDraw),
+            _ => Err(format!("expected a move, \"resign\" or \"draw\", got {:?}", answer).into()),
+        }
+    }
+
+    pub(crate) fn accept_draw<const N: usize>(&mut self, game: &Game<N>, timeout: Duration) -> Result<bool, Box<dyn Error>> {
+        self.send(&format!("draw {}", game.to_position_str()))?;
+        match self.receive(timeout)?.as_str() {
+            "accept" => Ok(true),
+            "decline" => Ok(false),
+            answer => Err(format!("expected \"accept\" or \"decline\", got {:?}", answer).into()),
+        }
+    }
+
+    /// Tells the bot to exit. It may already have.
+    pub(crate) fn quit(&mut self) {
+        let _ = self.send("quit");
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        if self.closed {
+            return Err(io::Error::other(match self.timed_out {
+                true => "the bot didn't answer an earlier request in time",
+                false => "connection closed",
+            }));
+        }
+        let sent = writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush());
+        self.closed |= sent.is_err();
+        sent
+    }
+
+    // The next line that isn't empty or a `#` comment
+    fn receive(&mut self, timeout: Duration) -> Result<String, Box<dyn Error>> {
+        loop {
+            let line = match self.lines.recv_timeout(timeout) {
+                Ok(Ok(line)) => line,
+                Ok(Err(err)) => {
+                    self.closed = true;
+                    return Err(err.into());
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.closed = true;
+                    self.timed_out = true;
+                    return Err(format!("no answer within {:?}", timeout).into());
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.closed = true;
+                    return Err("connection closed".into());
+                },
+            };
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                return Ok(line.to_string());
+            }
+        }
+    }
+}
+
+/// The bot side of the line protocol: answers requests read from `reader`
+/// with `player` until told to quit or the connection closes. Serves any
+/// `PlayerController` to a `ProcessPlayer` over stdio or to a `SocketPlayer`
+/// over TCP.
+pub fn serve_player<const N: usize>(reader: impl Read, mut writer: impl Write, name: &str, player: &mut dyn PlayerController<N>) -> Result<(), Box<dyn Error>> {
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let (request, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let answer = match request {
+            "wasmthello" => format!("ready {}", name),
+            "turn" => match player.take_turn(&Game::<N>::from_position_str(argument)?)? {
+                Turn::Move(pos) => format!("play {}", pos),
+                Turn::Resign => "resign".to_string(),
+                Turn::OfferDraw => "draw".to_string(),
+                Turn::Undo => return Err("the protocol has no way to take back moves".into()),
+            },
+            "draw" => match player.accept_draw(&Game::<N>::from_position_str(argument)?) {
+                true => "accept".to_string(),
+                false => "decline".to_string(),
+            },
+            "quit" => break,
+            _ => format!("# unknown request {:?}", request),
+        };
+        writeln!(writer, "{}", answer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::error::Error;
+use std::fmt;
+use crate::executor::Executor;
+use crate::game::{Player, PlayerController};
+use crate::matchrunner::{MatchConfig, MatchRecord, MatchRunner};
+use crate::opening::{Opening, OpeningSuite};
+
+/// A single scheduled game. Entrants are referred to by their index in the tournament.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pairing {
+    /// Identifies the game within the tournament. The seed of the game is
+    /// offset by it. For round robins this is the position in the schedule.
+    pub game: usize,
+    pub round: usize,
+    pub white: usize,
+    pub black: usize,
+    pub opening: Option<Opening>,
+}
+
+/// Constructor for the players of a tournament, given an entrant index.
+pub type PlayerFactory<'a, const N: usize> = dyn Fn(usize) -> Result<Box<dyn PlayerController<N>>, Box<dyn Error>> + Sync + 'a;
+
+/// The entrants, game config and threads of a tournament, shared by all
+/// formats and by `Sprt`.
+#[derive(Debug, Clone)]
+pub struct TournamentSettings {
+    pub(crate) entrants: Vec<String>,
+    pub(crate) config: MatchConfig,
+    pub(crate) threads: usize,
+}
+
+impl TournamentSettings {
+    /// Entrants are referred to by their index in `entrants`, e.g. when
+    /// creating their players.
+    pub fn new(entrants: Vec<String>) -> Self {
+        Self { entrants, config: MatchConfig::default(), threads: 1 }
+    }
+
+    /// Config for all games. The seed is offset by the number of the game,
+    /// and the opening is overridden in formats that choose their own.
+    pub fn config(mut self, config: MatchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Number of games played at the same time. Formats that play games in
+    /// groups, such as knockout matches or SPRT pairs, play that many groups
+    /// at the same time instead.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn entrants(&self) -> &[String] {
+        &self.entrants
+    }
+
+    /// The config for `pairing`, with its opening and the seed offset by its game.
+    pub fn match_config(&self, pairing: &Pairing) -> MatchConfig {
+        let mut config = self.config.clone();
+        config.opening = pairing.opening.clone().or(config.opening);
+        config.seed = config.seed.wrapping_add(pairing.game as u64);
+        config
+    }
+
+    pub(crate) fn executor(&self) -> Executor<'static> {
+        Executor::new(self.threads)
+    }
+}
+
+/// Schedules and plays games between a list of entrants.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    settings: TournamentSettings,
+    // (round, first, second), where first plays white in the first game of the pairing
+    matchups: Vec<(usize, usize, usize)>,
+    repeats: usize,
+    openings: Option<OpeningSuite>,
+}
+
+impl Tournament {
+    /// Every entrant plays every other entrant, scheduled in rounds where
+    /// each entrant plays at most once and white and black alternate.
+    pub fn round_robin(settings: TournamentSettings) -> Self {
+        let matchups = round_robin_rounds(settings.entrants.len()).into_iter().enumerate()
+            .flat_map(|(round, pairs)| pairs.into_iter().map(move |(a, b)| (round, a, b)))
+            .collect();
+        Self::new(settings, matchups)
+    }
+
+    /// The entrant at `contender` plays every other entrant, one per round.
+    /// This is what a new entrant joining an existing round robin has to play.
+    pub fn gauntlet(settings: TournamentSettings, contender: usize) -> Self {
+        assert!(contender < settings.entrants.len());
+        let matchups = (0..settings.entrants.len()).filter(|&i| i != contender)
+            .enumerate()
+            .map(|(round, opponent)| (round, contender, opponent))
+            .collect();
+        Self::new(settings, matchups)
+    }
+
+    fn new(settings: TournamentSettings, matchups: Vec<(usize, usize, usize)>) -> Self {
+        Self { settings, matchups, repeats: 1, openings: None }
+    }
+
+    /// Plays the whole schedule `repeats` times, swapping colours every repetition.
+    pub fn repeats(mut self, repeats: usize) -> Self {
+        self.repeats = repeats;
+        self
+    }
+
+    /// Plays every pairing once from each opening with either colour.
+    pub fn openings(mut self, openings: OpeningSuite) -> Self {
+        self.openings = Some(openings);
+        self
+    }
+
+    pub fn settings(&self) -> &TournamentSettings {
+        &self.settings
+    }
+
+    /// All games of the tournament, in order.
+    pub fn pairings(&self) -> Vec<Pairing> {
+        let rounds = self.matchups.iter().map(|m| m.0 + 1).max().unwrap_or(0);
+        let mut pairings = Vec::new();
+        for repeat in 0..self.repeats {
+            for &(round, first, second) in &self.matchups {
+                let round = repeat * rounds + round;
+                let (first, second) = if repeat % 2 == 0 { (first, second) } else { (second, first) };
+                match &self.openings {
+                    None => pairings.push((round, first, second, None)),
+                    Some(suite) => for opening in suite.openings() {
+                        pairings.push((round, first, second, Some(opening.clone())));
+                        pairings.push((round, second, first, Some(opening.clone())));
+                    },
+                }
+            }
+        }
+        pairings.into_iter().enumerate()
+            .map(|(game, (round, white, black, opening))| Pairing { game, round, white, black, opening })
+            .collect()
+    }
+
+    /// The config to play `pairing` with.
+    pub fn match_config(&self, pairing: &Pairing) -> MatchConfig {
+        self.settings.match_config(pairing)
+    }
+
+    /// Calls `play` for every pairing on the tournament's worker threads and
+    /// returns the results in schedule order.
+    pub fn play_all<R: Send>(&self, play: impl Fn(&Pairing) -> R + Sync) -> Vec<R> {
+        self.settings.executor().run(&self.pairings(), play)
+    }
+
+    /// Plays the tournament with players from `new_player` and computes the standings.
+    pub fn run<const N: usize>(&self, new_player: &PlayerFactory<N>) -> Result<TournamentResult, Box<dyn Error>> {
+        let games = self.play_all(|pairing| {
+            play_pairing(self.match_config(pairing), pairing, new_player)
+                .map(|record| (pairing.clone(), record))
+        }).into_iter().collect::<Result<Vec<_>, _>>()?;
+        let standings = Standings::new(&self.settings.entrants, &games, &[]);
+        Ok(TournamentResult { games, byes: Vec::new(), standings })
+    }
+}
+
+pub(crate) fn play_pairing<const N: usize>(config: MatchConfig, pairing: &Pairing, new_player: &PlayerFactory<N>) -> Result<MatchRecord, String> {
+    let mut white = new_player(pairing.white).map_err(|err| err.to_string())?;
+    let mut black = new_player(pairing.black).map_err(|err| err.to_string())?;
+    let record = MatchRunner::<N>::new(config)
+        .map_err(|err| err.to_string())?
+        .run(white.as_mut(), black.as_mut());
+    Ok(record)
+}
+
+// Circle method: entrant 0 stays in place while the others rotate around it.
+// An odd number of entrants gets a bye, which is left out of the pairs.
+fn round_robin_rounds(n: usize) -> Vec<Vec<(usize, usize)>> {
+    let size = n + n % 2;
+    let bye = if n % 2 == 1 { Some(n) } else { None };
+    let mut circle: Vec<usize> = (0..size).collect();
+    let mut rounds = Vec::new();
+    for round in 0..size.saturating_sub(1) {
+        let mut pairs = Vec::new();
+        for i in 0..size / 2 {
+            let (a, b) = (circle[i], circle[size - 1 - i]);
+            if Some(a) == bye || Some(b) == bye {
+                continue;
+            }
+            // The fixed entrant alternates colours, the rest play white from the top row
+            pairs.push(if i == 0 && round % 2 == 1 { (b, a) } else { (a, b) });
+        }
+        rounds.push(pairs);
+        circle[1..].rotate_right(1);
+    }
+    rounds
+}
+
+#[derive(Debug, Clone)]
+pub struct TournamentResult {
+    pub games: Vec<(Pairing, MatchRecord)>,
+    /// Entrants that sat out a round and were awarded a point for it, as (round, entrant).
+    pub byes: Vec<(usize, usize)>,
+    pub standings: Standings,
+}
+
+impl TournamentResult {
+    /// The pairings of each round, in order.
+    pub fn rounds(&self) -> Vec<Vec<&Pairing>> {
+        let mut rounds: Vec<Vec<&Pairing>> = Vec::new();
+        for (pairing, _) in &self.games {
+            if rounds.len() <= pairing.round {
+                rounds.resize_with(pairing.round + 1, Vec::new);
+            }
+            rounds[pairing.round].push(pairing);
+        }
+        rounds
+    }
+
+    /// Lists the pairings and results of every round, using the entrant names in `standings`.
+    pub fn format_rounds(&self) -> String {
+        let mut names = vec![""; self.standings.0.len()];
+        for standing in &self.standings.0 {
+            names[standing.entrant] = &standing.name;
+        }
+        let mut out = String::new();
+        for (round, pairings) in self.rounds().into_iter().enumerate() {
+            out += &format!("round {}\n", round + 1);
+            for pairing in pairings {
+                let record = &self.games.iter().find(|(p, _)| p.game == pairing.game).expect("played").1;
+                let result = match record.winner {
+                    Some(Player::White) => "1-0",
+                    Some(Player::Black) => "0-1",
+                    None => "1/2",
+                };
+                out += &format!("  {} - {} {}\n", names[pairing.white], names[pairing.black], result);
+            }
+            for &(_, entrant) in self.byes.iter().filter(|(r, _)| *r == round) {
+                out += &format!("  {} bye\n", names[entrant]);
+            }
+        }
+        out
+    }
+}
+
+/// An entrant's results in a tournament.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub entrant: usize,
+    pub name: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub byes: u32,
+    /// 1 point per win or bye, half per draw.
+    pub points: f64,
+    /// Sum of the points of beaten opponents plus half the points of drawn
+    /// opponents. The first tie-breaker.
+    pub sonneborn_berger: f64,
+    /// Own tiles minus opponent tiles over all games. The last tie-breaker.
+    pub disc_difference: i64,
+}
+
+/// Entrants ordered by points, then Sonneborn-Berger score, then number of
+/// wins, then disc difference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings(pub Vec<Standing>);
+
+impl Standings {
+    /// Each entrant in `byes` gets a point per occurrence.
+    pub fn new(entrants: &[String], games: &[(Pairing, MatchRecord)], byes: &[usize]) -> Self {
+        let mut standings: Vec<Standing> = entrants.iter().enumerate()
+            .map(|(entrant, name)| Standing {
+                entrant, name: name.clone(), played: 0, wins: 0, draws: 0, losses: 0, byes: 0,
+                points: 0.0, sonneborn_berger: 0.0, disc_difference: 0,
+            })
+            .collect();
+        for &entrant in byes {
+            standings[entrant].byes += 1;
+            standings[entrant].points += 1.0;
+        }
+        for (pairing, record) in games {
+            let difference = record.white_count as i64 - record.black_count as i64;
+            for (entrant, colour, difference) in [(pairing.white, Player::White, difference), (pairing.black, Player::Black, -difference)] {
+                let standing = &mut standings[entrant];
+                standing.played += 1;
+                standing.disc_difference += difference;
+                match record.winner {
+                    None => {
+                        standing.draws += 1;
+                        standing.points += 0.5;
+                    },
+                    Some(winner) if winner == colour => {
+                        standing.wins += 1;
+                        standing.points += 1.0;
+                    },
+                    Some(_) => standing.losses += 1,
+                }
+            }
+        }
+        let points: Vec<f64> = standings.iter().map(|s| s.points).collect();
+        for (pairing, record) in games {
+            let (white_sb, black_sb) = match record.winner {
+                None => (points[pairing.black] / 2.0, points[pairing.white] / 2.0),
+                Some(Player::White) => (points[pairing.black], 0.0),
+                Some(Player::Black) => (0.0, points[pairing.white]),
+            };
+            standings[pairing.white].sonneborn_berger += white_sb;
+            standings[pairing.black].sonneborn_berger += black_sb;
+        }
+        standings.sort_by(|a, b| b.points.total_cmp(&a.points)
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(b.wins.cmp(&a.wins))
+            .then(b.disc_difference.cmp(&a.disc_difference))
+            .then(a.entrant.cmp(&b.entrant)));
+        Standings(standings)
+    }
+}
+
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.0.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4);
+        writeln!(f, "{:>3} {:<width$} {:>6} {:>4} {:>4} {:>4} {:>4} {:>7} {:>6}",
+                 "#", "name", "points", "w", "d", "l", "n", "sb", "discs", width = width)?;
+        for (rank, s) in self.0.iter().enumerate() {
+            writeln!(f, "{:>3} {:<width$} {:>6.1} {:>4} {:>4} {:>4} {:>4} {:>7.2} {:>+6}",
+                     rank + 1, s.name, s.points, s.wins, s.draws, s.losses, s.played,
+                     s.sonneborn_berger, s.disc_difference, width = width)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use wasmthello::{Game, Player, PlayerController, Pos, Turn};
+
+// Pause between the frames of the flip animation
+const FRAME: Duration = Duration::from_millis(150);
+
+// Plies shown in the move history panel
+const HISTORY_LINES: usize = 12;
+
+const RESET: &str = "\x1b[0m";
+const BOARD: &str = "\x1b[42m";
+const CURSOR: &str = "\x1b[43m";
+const CURSOR_ILLEGAL: &str = "\x1b[41m";
+const LAST_MOVE: &str = "\x1b[46m";
+
+/// A human playing on a full screen, colored board drawn with ANSI escape
+/// codes. The cursor is moved with the arrow keys, legal moves are marked and
+/// moves are animated. Needs a Unix terminal, as `stty` puts it in raw mode
+/// while waiting for keys.
+pub struct TuiPlayer<const N: usize> {
+    cursor: Pos,
+    // Whether the alternate screen is in use, left again on drop
+    entered: bool,
+    // The last answer, with the move count and hash of the position it was
+    // given in, to tell when the same question comes again after a declined
+    // draw or refused undo
+    last_answer: Option<(Turn, usize, u64)>,
+}
+
+// A square as drawn in one frame
+#[derive(Copy, Clone, PartialEq)]
+enum Square {
+    Empty,
+    Legal,
+    Disc(Player),
+    // Halfway through flipping
+    Flipping,
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Char(char),
+    Interrupt,
+}
+
+impl<const N: usize> TuiPlayer<N> {
+    pub fn new() -> Self {
+        Self { cursor: Pos::new(N / 2, N / 2), entered: false, last_answer: None }
+    }
+
+    fn enter(&mut self) {
+        if !self.entered {
+            // Alternate screen and hidden cursor
+            print!("\x1b[?1049h\x1b[?25l");
+            self.entered = true;
+        }
+    }
+
+    // Asks for keys until the turn is decided
+    fn choose(&mut self, game: &Game<N>, history: &History<N>) -> Result<Turn, Box<dyn Error>> {
+        let position = (game.move_list().len(), game.zobrist_hash());
+        let (draw_declined, undo_refused) = match self.last_answer {
+            Some((Turn::OfferDraw, moves, hash)) if (moves, hash) == position => (true, false),
+            Some((Turn::Undo, moves, hash)) if (moves, hash) == position => (false, true),
+            _ => (false, false),
+        };
+        let mut message = if draw_declined {
+            "Your draw offer was declined".to_string()
+        } else if undo_refused {
+            "You have no move to take back".to_string()
+        } else {
+            String::new()
+        };
+        let legal_moves = game.legal_moves(game.current_player());
+        if !legal_moves.contains(&self.cursor) {
+            self.cursor = legal_moves[0];
+        }
+        let raw = RawMode::enter()?;
+        loop {
+            draw(game, &squares(game, true), history.last_move(), Some(self.cursor), history, &message)?;
+            message.clear();
+            let (x, y) = (self.cursor.x(), self.cursor.y());
+            let turn = match read_key()? {
+                Key::Up => Some(Pos::new(x, y.saturating_sub(1))),
+                Key::Down => Some(Pos::new(x, (y + 1).min(N - 1))),
+                Key::Left => Some(Pos::new(x.saturating_sub(1), y)),
+                Key::Right => Some(Pos::new((x + 1).min(N - 1), y)),
+                Key::Char('k') => Some(Pos::new(x, y.saturating_sub(1))),
+                Key::Char('j') => Some(Pos::new(x, (y + 1).min(N - 1))),
+                Key::Char('h') => Some(Pos::new(x.saturating_sub(1), y)),
+                Key::Char('l') => Some(Pos::new((x + 1).min(N - 1), y)),
+                Key::Enter | Key::Char(' ') if legal_moves.contains(&self.cursor) => {
+                    let mut after = game.clone();
+                    after.play(self.cursor);
+                    animate(game, &after, self.cursor)?;
+                    return Ok(self.answer(game, Turn::Move(self.cursor)));
+                },
+                Key::Enter | Key::Char(' ') => {
+                    message = format!("{} is not a legal move", self.cursor);
+                    None
+                },
+                Key::Char('u') if undo_refused || game.move_list().is_empty() => {
+                    message = "You have no move to take back".to_string();
+                    None
+                },
+                Key::Char('u') => return Ok(self.answer(game, Turn::Undo)),
+                Key::Char('d') if draw_declined => {
+                    message = "You already offered a draw this turn".to_string();
+                    None
+                },
+                Key::Char('d') => return Ok(self.answer(game, Turn::OfferDraw)),
+                Key::Char('r') => {
+                    draw(game, &squares(game, true), history.last_move(), None, history, "Resign? (y/n)")?;
+                    match read_key()? {
+                        Key::Char('y' | 'Y') => return Ok(self.answer(game, Turn::Resign)),
+                        Key::Interrupt => self.quit(raw),
+                        _ => None,
+                    }
+                },
+                Key::Interrupt => self.quit(raw),
+                Key::Char(_) => None,
+            };
+            if let Some(cursor) = turn {
+                self.cursor = cursor;
+            }
+        }
+    }
+
+    fn leave(&mut self) {
+        if self.entered {
+            print!("\x1b[?25h\x1b[?1049l");
+            let _ = io::stdout().flush();
+            self.entered = false;
+        }
+    }
+
+    // Ctrl-C ends the program, as it would outside raw mode, after putting
+    // the terminal back the way it was
+    fn quit(&mut self, raw: RawMode) -> ! {
+        drop(raw);
+        self.leave();
+        std::process::exit(130);
+    }
+
+    fn answer(&mut self, game: &Game<N>, turn: Turn) -> Turn {
+        self.last_answer = Some((turn, game.move_list().len(), game.zobrist_hash()));
+        turn
+    }
+}
+
+impl<const N: usize> Default for TuiPlayer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PlayerController<N> for TuiPlayer<N> {
+    fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        match self.take_turn(game)? {
+            Turn::Move(pos) => Ok(pos),
+            turn => Err(format!("expected a move, got {:?}", turn).into()),
+        }
+    }
+
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
+        self.enter();
+        let history = History::of(game);
+        // Show the opponent's move being made, if there is a new one
+        let new_move = self.last_answer.is_none_or(|(_, moves, _)| game.move_list().len() > moves);
+        if let (Some(before), Some(pos), true) = (&history.before_last, history.last_move(), new_move) {
+            animate(before, game, pos)?;
+        }
+        self.choose(game, &history)
+    }
+
+    fn accept_draw(&mut self, game: &Game<N>) -> bool {
+        let history = History::of(game);
+        self.enter();
+        let Ok(raw) = RawMode::enter() else {
+            return false;
+        };
+        let answer = draw(game, &squares(game, false), history.last_move(), None, &history, "Your opponent offers a draw, accept? (y/n)")
+            .and_then(|_| read_key());
+        match answer {
+            Ok(Key::Interrupt) => self.quit(raw),
+            answer => matches!(answer, Ok(Key::Char('y' | 'Y'))),
+        }
+    }
+}
+
+impl<const N: usize> Drop for TuiPlayer<N> {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+// The moves of a game, found by playing it again from the start
+struct History<const N: usize> {
+    moves: Vec<(Player, Pos)>,
+    // The position the last move was made in
+    before_last: Option<Game<N>>,
+}
+
+impl<const N: usize> History<N> {
+    fn of(game: &Game<N>) -> Self {
+        let mut replayed = Game::<N>::with_rules(game.rules());
+        let mut history = History { moves: Vec::new(), before_last: None };
+        for &offset in game.move_list() {
+            let pos = Pos::from_offset(offset, N);
+            if replayed.legal_moves(replayed.current_player()).is_empty() {
+                replayed.skip();
+            }
+            // Positions set up from a string have no moves to play again
+            if replayed.flips(pos).is_empty() {
+                return History { moves: Vec::new(), before_last: None };
+            }
+            history.moves.push((replayed.current_player(), pos));
+            history.before_last = Some(replayed.clone());
+            replayed.play(pos);
+        }
+        history
+    }
+
+    fn last_move(&self) -> Option<Pos> {
+        self.moves.last().map(|&(_, pos)| pos)
+    }
+}
+
+// Puts the terminal in raw mode, so that keys are read as they are pressed,
+// until dropped
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enter() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[self.saved.trim()]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed, the full screen board needs a terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_key() -> io::Result<Key> {
+    let mut buf = [0; 8];
+    loop {
+        let read = io::stdin().read(&mut buf)?;
+        return Ok(match &buf[..read] {
+            [] => return Err(io::ErrorKind::UnexpectedEof.into()),
+            [0x1b, b'[' | b'O', b'A'] => Key::Up,
+            [0x1b, b'[' | b'O', b'B'] => Key::Down,
+            [0x1b, b'[' | b'O', b'C'] => Key::Right,
+            [0x1b, b'[' | b'O', b'D'] => Key::Left,
+            [b'\r' | b'\n'] => Key::Enter,
+            // Ctrl-C, which raw mode delivers as a key
+            [3] => Key::Interrupt,
+            &[c] if c.is_ascii_graphic() || c == b' ' => Key::Char(c as char),
+            _ => continue,
+        });
+    }
+}
+
+fn squares<const N: usize>(game: &Game<N>, mark_legal_moves: bool) -> Vec<Square> {
+    let mut squares = vec![Square::Empty; N * N];
+    for y in 0..N {
+        for x in 0..N {
+            if let Some(player) = game.tile(Pos::new(x, y)) {
+                squares[x + y * N] = Square::Disc(player);
+            }
+        }
+    }
+    if mark_legal_moves {
+        for pos in game.legal_moves(game.current_player()) {
+            squares[pos.x() + pos.y() * N] = Square::Legal;
+        }
+    }
+    squares
+}
+
+// Shows `pos` being placed in `before` and the discs turning over, ending on `after`
+fn animate<const N: usize>(before: &Game<N>, after: &Game<N>, pos: Pos) -> io::Result<()> {
+    let history = History::of(after);
+    let mut frame = squares(before, false);
+    frame[pos.x() + pos.y() * N] = Square::Disc(before.current_player());
+    draw(before, &frame, Some(pos), None, &history, "")?;
+    thread::sleep(FRAME);
+    for y in 0..N {
+        for x in 0..N {
+            let square = Pos::new(x, y);
+            if square != pos && before.tile(square) != after.tile(square) {
+                frame[x + y * N] = Square::Flipping;
+            }
+        }
+    }
+    draw(before, &frame, Some(pos), None, &history, "")?;
+    thread::sleep(FRAME);
+    draw(after, &squares(after, false), Some(pos), None, &history, "")?;
+    thread::sleep(FRAME);
+    Ok(())
+}
+
+// Draws the board with the disc counts, whose turn it is and the latest moves
+// beside it, and the keys and `message` below
+fn draw<const N: usize>(game: &Game<N>, squares: &[Square], last_move: Option<Pos>, cursor: Option<Pos>, history: &History<N>, message: &str) -> io::Result<()> {
+    let mut panel = vec![
+        format!("Black {:>3}   White {:>3}", game.count(Player::Black), game.count(Player::White)),
+        format!("{:?} to move", game.current_player()),
+        String::new(),
+        "Moves".to_string(),
+    ];
+    let shown = history.moves.len().saturating_sub(HISTORY_LINES);
+    for (number, (player, pos)) in history.moves.iter().enumerate().skip(shown) {
+        panel.push(format!("{:>4}. {:<5} {}", number + 1, format!("{:?}", player), pos));
+    }
+
+    let mut lines = vec![format!("   {}", (0..N).map(|x| format!(" {} ", (b'a' + x as u8) as char)).collect::<String>())];
+    for y in 0..N {
+        let mut line = format!("{:>2} ", y + 1);
+        for x in 0..N {
+            let pos = Pos::new(x, y);
+            let background = match (cursor == Some(pos), squares[x + y * N]) {
+                (true, Square::Legal) => CURSOR,
+                (true, _) => CURSOR_ILLEGAL,
+                (false, _) if last_move == Some(pos) => LAST_MOVE,
+                _ => BOARD,
+            };
+            let glyph = match squares[x + y * N] {
+                Square::Empty => "   ".to_string(),
+                Square::Legal => "\x1b[93m · ".to_string(),
+                Square::Disc(Player::Black) => "\x1b[30m ● ".to_string(),
+                Square::Disc(Player::White) => "\x1b[97m ● ".to_string(),
+                Square::Flipping => "\x1b[90m ● ".to_string(),
+            };
+            line.push_str(&format!("{}{}", background, glyph));
+        }
+        line.push_str(RESET);
+        lines.push(line);
+    }
+
+    let mut screen = String::from("\x1b[H");
+    for row in 0..lines.len().max(panel.len()) {
+        let board = lines.get(row).map_or_else(|| " ".repeat(3 + 3 * N), |line| line.clone());
+        screen.push_str(&format!("{}   {}\x1b[K\r\n", board, panel.get(row).map_or("", |line| line)));
+    }
+    screen.push_str("\x1b[K\r\n");
+    screen.push_str("arrows/hjkl move  enter place  u undo  d offer draw  r resign  ctrl-c quit\x1b[K\r\n");
+    screen.push_str(&format!("{}\x1b[J", message));
+    let mut stdout = io::stdout();
+    stdout.write_all(screen.as_bytes())?;
+    stdout.flush()
+}
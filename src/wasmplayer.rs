@@ -1,75 +1,272 @@
 use std::error::Error;
 use crate::game::Game;
 use crate::game::Pos;
-use crate::game::PlayerController;
+use crate::game::{PlayerController, Turn};
+use crate::profile::ProfileReport;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 use wasmtime::*;
 
+// Bots are expected to answer the same way every time they see the same
+// position, so that stored games can be replayed. NaN bit patterns are
+// canonicalized, threads are disabled and every proposal is pinned rather
+// than left to whatever the wasmtime version enables by default.
+fn deterministic_config() -> Config {
+    let mut config = Config::new();
+    config.cranelift_nan_canonicalization(true)
+        .wasm_threads(false)
+        .wasm_simd(false)
+        .wasm_reference_types(false)
+        .wasm_bulk_memory(true)
+        .wasm_multi_value(true)
+        .wasm_multi_memory(false)
+        .wasm_module_linking(false)
+        .wasm_memory64(false);
+    config
+}
+
+// The engine shared by all `WasmPlayer`s.
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| Engine::new(&deterministic_config()).expect("invalid wasmtime config"))
+}
+
+// Same as `engine`, but metering fuel and interruptable so that profiling
+// players can measure where the bot spends its time.
+fn profiling_engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = deterministic_config();
+        config.consume_fuel(true)
+            .interruptable(true);
+        Engine::new(&config).expect("invalid wasmtime config")
+    })
+}
+
+type AnswerFunc = TypedFunc<(i32, i32, i32, i32, i32), i32>;
+type DrawOfferedFunc = TypedFunc<(i32, i32, i32), i32>;
+
+// Values `answer` can return instead of a move
+const RESIGN: i32 = -1;
+const OFFER_DRAW: i32 = -2;
+
+struct Instantiated {
+    store: Store<()>,
+    memory: Memory,
+    func: AnswerFunc,
+    draw_offered: Option<DrawOfferedFunc>,
+    wasm_memory_offset: i32,
+    // Exported mutable globals, restored along with memory when profiling
+    globals: Vec<(String, Global)>,
+}
+
+fn instantiate(module: &Module, buf_len: usize) -> Result<Instantiated, Box<dyn Error>> {
+    let mut store = Store::new(module.engine(), ());
+    if store.fuel_consumed().is_some() {
+        store.add_fuel(u64::MAX)?;
+    }
+    let instance = Instance::new(&mut store, module, &[])?;
+
+    let alloc = instance.get_func(&mut store, "alloc_wasm_memory")
+        .ok_or("`alloc_wasm_memory` was not an exported function")?;
+    let alloc = alloc.typed::<i32, i32, _>(&store)?;
+
+    // Get linear memory. By using an export and letting the wasm module handle
+    // allocations we avoid writing to memory from host side without guest
+    // being aware of it. It also seems quite tricky to compile languages to
+    // wasm and get it to use imported memory.
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or("failed to find `memory` export")?;
+
+    let answer = instance.get_func(&mut store, "answer")
+        .ok_or("`answer` was not an exported function")?
+        .typed::<(i32, i32, i32, i32, i32), i32, _>(&store)?;
+
+    // Optional, bots without it decline every draw offer
+    let draw_offered = instance.get_func(&mut store, "draw_offered")
+        .map(|func| func.typed::<(i32, i32, i32), i32, _>(&store))
+        .transpose()?;
+
+    let exported: Vec<(String, Global)> = instance.exports(&mut store)
+        .filter_map(|export| {
+            let name = export.name().to_string();
+            export.into_global().map(|global| (name, global))
+        })
+        .collect();
+    let globals = exported.into_iter()
+        .filter(|(_, global)| global.ty(&store).mutability() == Mutability::Var)
+        .collect();
+
+    // Board occupies N*N, legal moves never occupy more than N*N.
+    // This is all the memory we'll use, so we don't need the alloc
+    // function anymore. We'll use it for the entire duration of the
+    // game, so no need for a dealloc function.
+    let ptr = alloc.call(&mut store, buf_len as i32)?;
+    Ok(Instantiated { store, memory, func: answer, draw_offered, wasm_memory_offset: ptr, globals })
+}
+
+// Samples which function a move spends its time in. Execution is
+// deterministic, so a fresh instance restored to the memory and exported
+// globals the real call started from takes the same path as the real call
+// did. State in globals the bot doesn't export can't be restored, so for bots
+// keeping e.g. their allocator state there the paths may differ. Interrupting
+// the fresh instance part way through traps with a backtrace, and the
+// innermost frame of that backtrace is the sample. Fuel traps don't carry a wasm backtrace, so the
+// samples are spread over the measured time of the move rather than its fuel.
+// Memory and exported globals of the bot when a call starts
+type CallState = (Vec<u8>, Vec<(String, Val)>);
+
+struct Profiler {
+    samples_per_move: u32,
+    report: ProfileReport,
+}
+
+impl Profiler {
+    fn sample_move(&mut self, module: &Module, state: &CallState, args: (i32, i32, i32, i32, i32), time: Duration) {
+        let samples = self.samples_per_move;
+        for i in 1..=samples {
+            let delay = time * i / (samples + 1);
+            if let Some(function) = Self::sample(module, state, args, delay) {
+                self.report.record_sample(function);
+            }
+        }
+    }
+
+    fn sample(module: &Module, (memory, globals): &CallState, args: (i32, i32, i32, i32, i32), delay: Duration) -> Option<String> {
+        let mut instance = instantiate(module, 0).ok()?;
+        let pages = (memory.len() / (64 * 1024)) as u64;
+        let current_pages = instance.memory.size(&instance.store);
+        if pages > current_pages {
+            instance.memory.grow(&mut instance.store, pages - current_pages).ok()?;
+        }
+        instance.memory.write(&mut instance.store, 0, memory).ok()?;
+        for (name, value) in globals {
+            let (_, global) = instance.globals.iter().find(|(global_name, _)| global_name == name)?;
+            global.set(&mut instance.store, value.clone()).ok()?;
+        }
+
+        let handle = instance.store.interrupt_handle().ok()?;
+        let timer = thread::spawn(move || {
+            thread::sleep(delay);
+            handle.interrupt();
+        });
+        let result = instance.func.call(&mut instance.store, args);
+        timer.join().ok()?;
+        match result {
+            Ok(_) => None, // finished before the interrupt arrived
+            Err(trap) => trap.trace().first().map(|frame| match frame.func_name() {
+                Some(name) => name.to_string(),
+                None => format!("func[{}]", frame.func_index()),
+            }),
+        }
+    }
+}
+
 pub struct WasmPlayer<const N: usize> where [(); N*N*2]: Sized {
     store: Store<()>,
     memory: Memory,
-    func: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+    func: AnswerFunc,
+    draw_offered: Option<DrawOfferedFunc>,
     buf: [u8; N*N*2],
     wasm_memory_offset: i32,
+    globals: Vec<(String, Global)>,
+    module: Module,
+    profiler: Option<Profiler>,
 }
 
 impl<const N: usize> WasmPlayer<N> where [(); N*N*2]: Sized {
     pub fn new(wasm: &[u8]) -> Result<Self, Box<dyn Error>> {
-        let engine = Engine::default();
-
-        let module = Module::new(&engine, wasm)?;
-        let mut store = Store::new(&engine, ());
-        let instance = Instance::new(&mut store, &module, &[])?;
-
-        let alloc = instance.get_func(&mut store, "alloc_wasm_memory")
-            .expect("`alloc_wasm_memory` was not an exported function");
-        let alloc = alloc.typed::<i32, i32, _>(&store)?;
-
-        // Get linear memory. By using an export and letting the wasm module handle
-        // allocations we avoid writing to memory from host side without guest
-        // being aware of it. It also seems quite tricky to compile languages to
-        // wasm and get it to use imported memory.
-        let memory = instance
-            .get_memory(&mut store, "memory")
-            .expect("failed to find `memory` export");
-
-        let answer = instance.get_func(&mut store, "answer")
-            .expect("`answer` was not an exported function")
-            .typed::<(i32, i32, i32, i32, i32), i32, _>(&store)?;
-
-        // Board occupies N*N, legal moves never occupy more than N*N.
-        // This is all the memory we'll use, so we don't need the alloc
-        // function anymore. We'll use it for the entire duration of the
-        // game, so no need for a dealloc function.
-        let ptr = alloc.call(&mut store, (N*N*2) as i32)?;
+        let module = Module::new(engine(), wasm)?;
+        Self::from_module(module, None)
+    }
+
+    /// Creates a player that records fuel and time spent per move, and samples
+    /// which functions are hottest. Each sample replays part of a move on a
+    /// fresh instance, so moves get roughly `samples_per_move / 2` times slower.
+    pub fn with_profiling(wasm: &[u8], samples_per_move: u32) -> Result<Self, Box<dyn Error>> {
+        let module = Module::new(profiling_engine(), wasm)?;
+        Self::from_module(module, Some(Profiler { samples_per_move, report: ProfileReport::default() }))
+    }
+
+    fn from_module(module: Module, profiler: Option<Profiler>) -> Result<Self, Box<dyn Error>> {
+        let instance = instantiate(&module, N*N*2)?;
         Ok(Self {
-            store, memory, func: answer, buf: [0; N*N*2], wasm_memory_offset: ptr,
+            store: instance.store, memory: instance.memory, func: instance.func,
+            draw_offered: instance.draw_offered,
+            buf: [0; N*N*2], wasm_memory_offset: instance.wasm_memory_offset,
+            globals: instance.globals, module, profiler,
         })
     }
+
+    /// The profile of the moves made so far, if this player was created with profiling.
+    pub fn profile_report(&self) -> Option<&ProfileReport> {
+        self.profiler.as_ref().map(|profiler| &profiler.report)
+    }
 }
 
 impl<const N: usize> PlayerController<N> for WasmPlayer<N> where [(); N*N*2]: Sized {
 
     fn make_play(&mut self, game: &Game<N>) -> Result<Pos, Box<dyn Error>> {
+        match self.take_turn(game)? {
+            Turn::Move(pos) => Ok(pos),
+            turn => Err(format!("expected a move, got {:?}", turn).into()),
+        }
+    }
+
+    fn take_turn(&mut self, game: &Game<N>) -> Result<Turn, Box<dyn Error>> {
         game.serialize(&mut self.buf); // Write the first N*N bytes
-        game.print();
 
         let legal_moves = game.legal_moves(game.current_player());
         let legal_move_count = legal_moves.len();
         assert!(legal_move_count > 0);
         for (i, pos) in legal_moves.into_iter().enumerate() {
             let offset = pos.to_offset(N);
-            println!("legal move {}: {:?}", i, pos);
             self.buf[N*N+i] = offset;
         }
         self.memory.write(&mut self.store, self.wasm_memory_offset as usize, &self.buf)?;
 
-        let ans = self.func.call(&mut self.store,
-                                 (self.wasm_memory_offset as i32,
-                                  N as i32,
-                                  self.wasm_memory_offset + (N*N) as i32,
-                                  legal_move_count as i32,
-                                  game.current_player().serialize() as i32,
-                                  ))?;
-        Ok(Pos::from_offset(ans as u8, N))
+        let args = (self.wasm_memory_offset,
+                    N as i32,
+                    self.wasm_memory_offset + (N*N) as i32,
+                    legal_move_count as i32,
+                    game.current_player().serialize() as i32,
+                    );
+        // What the profiler restores its sampling instances to
+        let state = self.profiler.as_ref().map(|_| {
+            let globals = self.globals.iter().map(|(name, global)| (name.clone(), global.get(&mut self.store))).collect();
+            (self.memory.data(&self.store).to_vec(), globals)
+        });
+        let fuel_before = self.store.fuel_consumed().unwrap_or(0);
+        let start = Instant::now();
+        let ans = self.func.call(&mut self.store, args)?;
+        if let (Some(profiler), Some(state)) = (self.profiler.as_mut(), state) {
+            let time = start.elapsed();
+            let fuel = self.store.fuel_consumed().unwrap_or(0) - fuel_before;
+            profiler.report.record_move(fuel, time);
+            profiler.sample_move(&self.module, &state, args, time);
+        }
+        match ans {
+            RESIGN => Ok(Turn::Resign),
+            OFFER_DRAW => Ok(Turn::OfferDraw),
+            0..=255 => Ok(Turn::Move(Pos::from_offset(ans as u8, N))),
+            _ => Err(format!("invalid answer {}", ans).into()),
+        }
+    }
+
+    fn accept_draw(&mut self, game: &Game<N>) -> bool {
+        let Some(draw_offered) = self.draw_offered else {
+            return false;
+        };
+        game.serialize(&mut self.buf);
+        if self.memory.write(&mut self.store, self.wasm_memory_offset as usize, &self.buf[..N*N]).is_err() {
+            return false;
+        }
+        // The opponent of the player to move is the one answering
+        let player = game.current_player().flip();
+        let args = (self.wasm_memory_offset, N as i32, player.serialize() as i32);
+        // A trap is treated as declining
+        matches!(draw_offered.call(&mut self.store, args), Ok(answer) if answer != 0)
     }
 }
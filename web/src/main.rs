@@ -1,7 +1,7 @@
 #![feature(generic_const_exprs)]
 use axum::{
     error_handling::HandleErrorLayer,
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
@@ -22,32 +22,47 @@ use tower::{BoxError, ServiceBuilder};
 use wasmthello;
 use wasmthello::WasmPlayer;
 use wasmthello::Player;
+use wasmthello::{reference_player, Game, PlayerController, Turn};
+use wasmthello::{Elo, Executor, GameRecord, Glicko2, MatchConfig, MatchRunner, OpeningSuite, Rules, Tournament, TournamentSettings};
 use std::thread;
 
 #[tokio::main]
 async fn main() {
     let bot_db = BotDb::default();
     let game_db = GameDb::default();
+    {
+        let mut bot_map = bot_db.write().unwrap();
+        for &(name, player) in HOUSE_BOTS {
+            bot_map.insert(name, Bot::new(name, HOUSE_CREATOR, &[], Some(player), false));
+        }
+    }
 
+    // Replays take as long as the games did, so they get a longer limit
+    let verify = Router::new()
+        .route("/game/:id/verify", get(verify_game))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_error))
+                .timeout(VERIFY_TIMEOUT)
+        );
     let app = Router::new()
         .route("/games", get(index))
         .route("/game/:id", get(game_stats))
+        .route("/game/:id/profile", get(game_profile))
+        .route("/game/:id/record", get(game_record))
         .route("/bots", get(bots))
+        .route("/leaderboard", get(leaderboard))
+        .route("/analyse", get(analyse))
         .route("/new-bot", post(new_bot))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_error))
+                .timeout(REQUEST_TIMEOUT)
+        )
+        .merge(verify)
         // Add middleware to all routes
         .layer(
             ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|error: BoxError| async move {
-                    if error.is::<tower::timeout::error::Elapsed>() {
-                        Ok(StatusCode::REQUEST_TIMEOUT)
-                    } else {
-                        Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Unhandled internal error: {}", error),
-                        ))
-                    }
-                }))
-                .timeout(Duration::from_secs(10))
                 .layer(Extension(bot_db))
                 .layer(Extension(game_db))
                 .into_inner(),
@@ -61,6 +76,21 @@ async fn main() {
         .unwrap();
 }
 
+// Time limit for requests, except for `verify_game`
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(120);
+
+async fn handle_error(error: BoxError) -> Result<StatusCode, (StatusCode, String)> {
+    if error.is::<tower::timeout::error::Elapsed>() {
+        Ok(StatusCode::REQUEST_TIMEOUT)
+    } else {
+        Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unhandled internal error: {}", error),
+        ))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct CreateBot {
     name: String,
@@ -69,6 +99,9 @@ struct CreateBot {
     // or the byte representation of the text format, so accept either here.
     #[serde(deserialize_with = "deserialize_string_or_byte_array")]
     wasm: Box<[u8]>,
+    // Profile the bot's games, see `game_profile`
+    #[serde(default)]
+    profile: bool,
 }
 #[derive(Debug, Deserialize, Serialize, Clone, Copy)]
 struct Bot<'a> {
@@ -78,8 +111,94 @@ struct Bot<'a> {
     wins: u32,
     losses: u32,
     ties: u32,
+    profile: bool,
+    // Glicko-2 rating and deviation, see `battle_bots`
+    rating: f64,
+    deviation: f64,
+    #[serde(skip)]
+    volatility: f64,
+    elo: f64,
+    // Reference players behind a house bot for each board size, see `HOUSE_BOTS`
+    #[serde(borrow)]
+    house: Option<[&'a str; 3]>,
+    // Only handed to the creator on upload, see `game_profile`
+    #[serde(skip)]
+    secret: &'a str,
+}
+
+// The response to an upload, the only place the bot's secret is shown
+#[derive(Debug, Serialize)]
+struct NewBot<'a> {
+    #[serde(flatten)]
+    bot: Bot<'a>,
+    secret: &'a str,
+}
+
+impl<'a> Bot<'a> {
+    fn new(name: &'a str, creator: &'a str, wasm: &'a [u8], house: Option<[&'a str; 3]>, profile: bool) -> Self {
+        let glicko = Glicko2::default();
+        Bot { wins: 0, losses: 0, ties: 0, profile, name, creator, wasm,
+            rating: glicko.rating, deviation: glicko.deviation, volatility: glicko.volatility,
+            elo: Elo::default().rating, house,
+            secret: string_to_static_str(Uuid::new_v4().to_string()),
+        }
+    }
+
+    // A fresh instance of the bot to play a game with
+    fn player<const N: usize>(&self) -> Result<Box<dyn PlayerController<N>>, String> where [(); N*N*2]: Sized {
+        match self.house {
+            Some(house) => {
+                let size = BOARD_SIZES.iter().position(|&size| size == N).ok_or(format!("unsupported board size {}", N))?;
+                reference_player::<N>(house[size]).map_err(|err| err.to_string())
+            },
+            None => Ok(Box::new(WasmPlayer::<N>::new(self.wasm).map_err(|err| err.to_string())?)),
+        }
+    }
+
+    fn glicko(&self) -> Glicko2 {
+        Glicko2 { rating: self.rating, deviation: self.deviation, volatility: self.volatility }
+    }
+
+    fn set_glicko(&mut self, glicko: Glicko2) {
+        self.rating = glicko.rating;
+        self.deviation = glicko.deviation;
+        self.volatility = glicko.volatility;
+    }
+}
+
+// Board sizes bots play on, in the order of the specs in `HOUSE_BOTS`
+const BOARD_SIZES: [usize; 3] = [8, 12, 16];
+
+// Built-in reference players every new bot plays against, by name and
+// `reference_player` spec for each of `BOARD_SIZES`. Each plays a game in
+// about a second at most, so that uploads finish quickly.
+const HOUSE_BOTS: &[(&str, [&str; 3])] = &[
+    ("house-random", ["random"; 3]),
+    ("house-greedy", ["greedy"; 3]),
+    ("house-positional", ["positional"; 3]),
+    ("house-alphabeta", ["alphabeta:3"; 3]),
+    // Playouts rather than time, so that its games can be verified. A
+    // playout costs more the bigger the board.
+    ("house-mcts", ["mcts:500", "mcts:50", "mcts:10"]),
+];
+const HOUSE_CREATOR: &str = "house";
+
+// Every pairing is played from the same openings, with both colours, so
+// that deterministic bots don't just play the same two games over and over.
+const OPENING_COUNT: usize = 4;
+const OPENING_PLIES: usize = 4;
+const OPENING_SEED: u64 = 0;
+
+fn openings<const N: usize>() -> OpeningSuite {
+    OpeningSuite::random::<N>(OPENING_COUNT, OPENING_PLIES, Rules::default(), OPENING_SEED)
 }
 
+// Games of a new bot played at the same time, per board size.
+const GAME_THREADS: usize = 2;
+
+// Samples taken per move when profiling. Each one replays part of the move.
+const PROFILE_SAMPLES_PER_MOVE: u32 = 8;
+
 async fn new_bot(
     Json(input): Json<CreateBot>,
     Extension(db): Extension<BotDb<'static>>,
@@ -92,10 +211,7 @@ async fn new_bot(
         WasmPlayer::<8>::new(&input.wasm.clone()).map_err(|err|
             (StatusCode::BAD_REQUEST, format!("invalid wasm {}", err.to_string())))?;
         let name = string_to_static_str(input.name);
-        let bot = Bot { wins: 0, losses: 0, ties: 0,
-            name, creator: string_to_static_str(input.creator),
-            wasm: Box::leak(input.wasm)
-        };
+        let bot = Bot::new(name, string_to_static_str(input.creator), Box::leak(input.wasm), None, input.profile);
         let bots: Vec<&'static str> = bot_map.keys().map(|s| *s).collect(); // fetching the existing bot names while lock is still held prevents duplicated battles
         bot_map.insert(name, bot.clone());
         drop(bot_map);
@@ -118,7 +234,7 @@ async fn new_bot(
             let bots = bots.clone();
         thread::spawn(move || battle_bots::<16>(db.clone(), game_db.clone(), bot.clone(), bots.clone()));
         }
-        Ok((StatusCode::CREATED, Json(bot)))
+        Ok((StatusCode::CREATED, Json(NewBot { bot, secret: bot.secret })))
     }
 }
 
@@ -157,6 +273,34 @@ async fn bots(
     (StatusCode::OK, Json(list))
 }
 
+#[derive(Debug, Serialize)]
+struct LeaderboardEntry<'a> {
+    rank: usize,
+    name: &'a str,
+    creator: &'a str,
+    rating: f64,
+    deviation: f64,
+    elo: f64,
+    wins: u32,
+    losses: u32,
+    ties: u32,
+}
+
+// Bots ordered by Glicko-2 rating, best first
+async fn leaderboard(
+    Extension(db): Extension<BotDb<'static>>,
+) -> impl IntoResponse {
+    let mut bots: Vec<Bot> = db.read().unwrap().values().copied().collect();
+    bots.sort_by(|a, b| b.rating.total_cmp(&a.rating).then(a.deviation.total_cmp(&b.deviation)));
+    let list: Vec<LeaderboardEntry> = bots.iter().enumerate()
+        .map(|(i, bot)| LeaderboardEntry {
+            rank: i + 1, name: bot.name, creator: bot.creator,
+            rating: bot.rating, deviation: bot.deviation, elo: bot.elo,
+            wins: bot.wins, losses: bot.losses, ties: bot.ties,
+        }).collect();
+    (StatusCode::OK, Json(list))
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct GameResult<'a> {
     uuid: Uuid,
@@ -166,6 +310,16 @@ struct GameResult<'a> {
     moves: Box<[u8]>,
     board_size: usize,
     misplay: bool,
+    // How the game ended, e.g. by resignation
+    ending: String,
+    opening: Option<String>,
+    seed: u64,
+    // Profiled bot and its report, see `game_profile`
+    #[serde(skip)]
+    profile: Option<(&'a str, String)>,
+    // The game in the text record format, see `game_record`
+    #[serde(skip)]
+    record: String,
 }
 
 async fn game_stats(
@@ -177,51 +331,222 @@ async fn game_stats(
     Ok((StatusCode::OK, Json(result)))
 }
 
+// The game as a text record, to archive or load it elsewhere
+async fn game_record(
+    Path(id): Path<Uuid>,
+    Extension(db): Extension<GameDb<'static>>,
+) -> Result<(StatusCode, String), StatusCode> {
+    let record = db.read().unwrap().get(&id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .record.clone();
+    Ok((StatusCode::OK, record))
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyseRequest {
+    // In the format of `Game::to_position_str`
+    position: String,
+    // Bot to ask for its move in the position
+    bot: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Analysis {
+    board_size: usize,
+    to_move: &'static str,
+    white_count: usize,
+    black_count: usize,
+    legal_moves: Vec<String>,
+    game_over: bool,
+    winner: Option<&'static str>,
+    bot_answer: Option<String>,
+}
+
+async fn analyse(
+    Query(request): Query<AnalyseRequest>,
+    Extension(db): Extension<BotDb<'static>>,
+) -> Result<(StatusCode, Json<Analysis>), (StatusCode, String)> {
+    let bot = match &request.bot {
+        Some(name) => Some(*db.read().unwrap().get(name.as_str())
+            .ok_or((StatusCode::NOT_FOUND, format!("no bot named {}", name)))?),
+        None => None,
+    };
+    // The bot's move runs on a blocking thread, it may take as long as a turn
+    let analysis = tokio::task::spawn_blocking(move || {
+        let position = request.position;
+        let size = position.split_once(':').map_or("", |(size, _)| size);
+        match size {
+            "8" => analyse_position::<8>(&position, bot),
+            "12" => analyse_position::<12>(&position, bot),
+            "16" => analyse_position::<16>(&position, bot),
+            _ => Err(format!("unsupported board size {:?}", size)),
+        }
+    }).await.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    analysis.map(|analysis| (StatusCode::OK, Json(analysis)))
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))
+}
+
+fn analyse_position<const N: usize>(position: &str, bot: Option<Bot>) -> Result<Analysis, String> where [(); N*N*2]: Sized {
+    let game = Game::<N>::from_position_str(position).map_err(|err| err.to_string())?;
+    let legal_moves = game.legal_moves(game.current_player());
+    let game_over = game.game_over();
+    let bot_answer = match bot {
+        Some(bot) if !legal_moves.is_empty() => {
+            let mut player = bot.player::<N>()?;
+            Some(match player.take_turn(&game) {
+                Ok(Turn::Move(pos)) => pos.to_string(),
+                Ok(Turn::OfferDraw) => "draw offer".to_string(),
+                Ok(Turn::Resign) => "resign".to_string(),
+                Ok(Turn::Undo) => "undo".to_string(),
+                Err(err) => format!("error: {}", err),
+            })
+        },
+        _ => None,
+    };
+    Ok(Analysis {
+        board_size: N,
+        to_move: colour_name(game.current_player()),
+        white_count: game.count(Player::White),
+        black_count: game.count(Player::Black),
+        legal_moves: legal_moves.iter().map(|pos| pos.to_string()).collect(),
+        game_over,
+        winner: if game_over { game.winner().map(colour_name) } else { None },
+        bot_answer,
+    })
+}
+
+fn colour_name(player: Player) -> &'static str {
+    match player {
+        Player::White => "white",
+        Player::Black => "black",
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileRequest {
+    // The secret returned when the profiled bot was uploaded
+    secret: String,
+}
+
+// The profile report of a game with a profiled bot, for the bot's creator only
+async fn game_profile(
+    Path(id): Path<Uuid>,
+    Query(request): Query<ProfileRequest>,
+    Extension(db): Extension<BotDb<'static>>,
+    Extension(game_db): Extension<GameDb<'static>>,
+) -> Result<(StatusCode, String), StatusCode> {
+    let (bot_name, report) = game_db.read().unwrap().get(&id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .profile.clone().ok_or(StatusCode::NOT_FOUND)?;
+    let secret = db.read().unwrap().get(bot_name).ok_or(StatusCode::NOT_FOUND)?.secret;
+    if secret != request.secret {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok((StatusCode::OK, report))
+}
+
+// Reruns a stored game between fresh instances of its bots and checks that
+// every move is identical to the recording.
+async fn verify_game(
+    Path(id): Path<Uuid>,
+    Extension(db): Extension<BotDb<'static>>,
+    Extension(game_db): Extension<GameDb<'static>>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    let result = game_db.read().unwrap().get(&id)
+        .cloned().ok_or((StatusCode::NOT_FOUND, format!("no game with id {}", id)))?;
+    let (white, black) = {
+        let read = db.read().unwrap();
+        (*read.get(result.white_player).expect("bot removed?"),
+         *read.get(result.black_player).expect("bot removed?"))
+    };
+    // Replaying runs both bots for a whole game, so it gets a blocking thread
+    let verified = tokio::task::spawn_blocking(move || {
+        let opening = result.opening.as_deref();
+        match result.board_size {
+            8 => replay_bots::<8>(white, black, opening, result.seed, &result.moves),
+            12 => replay_bots::<12>(white, black, opening, result.seed, &result.moves),
+            16 => replay_bots::<16>(white, black, opening, result.seed, &result.moves),
+            size => Err(format!("unsupported board size {}", size)),
+        }
+    }).await.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    match verified {
+        Ok(()) => Ok((StatusCode::OK, "replay identical".to_string())),
+        Err(err) => Err((StatusCode::CONFLICT, err)),
+    }
+}
+
+fn replay_bots<const N: usize>(white: Bot, black: Bot, opening: Option<&str>, seed: u64, moves: &[u8]) -> Result<(), String> where [(); N*N*2]: Sized {
+    let mut config = MatchConfig::default().seed(seed);
+    if let Some(id) = opening {
+        let opening = openings::<N>().openings().iter().find(|o| o.id == id)
+            .cloned().ok_or(format!("unknown opening {}", id))?;
+        config = config.opening(opening);
+    }
+    let mut runner = MatchRunner::<N>::new(config).map_err(|err| err.to_string())?;
+    let mut white_player = white.player::<N>()?;
+    let mut black_player = black.player::<N>()?;
+    wasmthello::verify_replay(&mut runner, white_player.as_mut(), black_player.as_mut(), moves)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn play_match<'a, const N: usize>(config: MatchConfig, white: (&'a str, &mut dyn PlayerController<N>), black: (&'a str, &mut dyn PlayerController<N>)) -> GameResult<'a> {
+    let record = MatchRunner::<N>::new(config)
+        .expect("generated openings are legal")
+        .run(white.1, black.1);
+    let winner = match record.winner {
+        Some(Player::White) => white.0,
+        Some(Player::Black) => black.0,
+        None => "Tie"
+    };
+    let game_record = GameRecord::from_match_record::<N>(&record, white.0, black.0).to_string();
+    GameResult {
+        uuid: Uuid::new_v4(),
+        white_player: white.0,
+        black_player: black.0,
+        winner,
+        moves: record.moves.into_boxed_slice(),
+        board_size: N,
+        misplay: record.forfeit.is_some(),
+        ending: record.ending.to_string(),
+        opening: record.opening.map(|opening| opening.id),
+        seed: record.seed,
+        profile: None,
+        record: game_record,
+    }
+}
+
 fn battle_bots<'a, const N: usize>(db: BotDb<'a>, game_db: GameDb<'a>, contender: Bot<'a>, bot_list: Vec<&'a str>) -> Option<()> where [(); N*N*2]: Sized {
     let bots = {
         let read = db.read().unwrap();
-        bot_list.into_iter().map(|name| (name, read.get(&name).expect("bot removed?").wasm))
+        bot_list.into_iter().map(|name| *read.get(&name).expect("bot removed?"))
             .collect::<Vec<_>>()
     };
-    let white_results = bots.iter().map(|params| {
-        let name = params.0;
-        let mut contender_player = WasmPlayer::<N>::new(&contender.wasm).unwrap();
-        let mut opponent_player = WasmPlayer::<N>::new(&params.1).unwrap();
-        let game = wasmthello::play_game(&mut contender_player, &mut opponent_player);
-        let winner = match game.winner() {
-            Some(Player::White) => contender.name,
-            Some(Player::Black) => name,
-            None => "Tie"
-        };
-        GameResult {
-            uuid: Uuid::new_v4(),
-            white_player: contender.name,
-            black_player: name,
-            winner,
-            moves: game.move_list().clone().into_boxed_slice(),
-            board_size: N,
-            misplay: game.is_misplay(),
-        }});
-    let black_results = bots.iter().map(|params| {
-        let name = params.0;
-        let mut contender_player = WasmPlayer::<N>::new(&contender.wasm).unwrap();
-        let mut opponent_player = WasmPlayer::<N>::new(&params.1).unwrap();
-        let game = wasmthello::play_game(&mut opponent_player, &mut contender_player);
-        let winner = match game.winner() {
-            Some(Player::White) => name,
-            Some(Player::Black) => contender.name,
-            None => "Tie"
+    // The contender is entrant 0, the existing bots follow in order
+    let entrants = std::iter::once(contender.name).chain(bots.iter().map(|b| b.name))
+        .map(|name| name.to_string()).collect();
+    let tournament = Tournament::gauntlet(TournamentSettings::new(entrants), 0)
+        .openings(openings::<N>());
+    let new_contender_player = || if contender.profile {
+        WasmPlayer::<N>::with_profiling(contender.wasm, PROFILE_SAMPLES_PER_MOVE).unwrap()
+    } else {
+        WasmPlayer::<N>::new(contender.wasm).unwrap()
+    };
+    let executor = Executor::new(GAME_THREADS).on_progress(|progress| println!(
+        "{} on {}x{}: {}/{} games played", contender.name, N, N, progress.completed, progress.total));
+    let results = executor.run(&tournament.pairings(), |pairing| {
+        // Every pairing in the gauntlet is the contender against one other bot
+        let opponent = bots[pairing.white.max(pairing.black) - 1];
+        let mut contender_player = new_contender_player();
+        let mut opponent_player = opponent.player::<N>().unwrap();
+        let mut result = if pairing.white == 0 {
+            play_match(tournament.match_config(pairing), (contender.name, &mut contender_player), (opponent.name, opponent_player.as_mut()))
+        } else {
+            play_match(tournament.match_config(pairing), (opponent.name, opponent_player.as_mut()), (contender.name, &mut contender_player))
         };
-        GameResult {
-            uuid: Uuid::new_v4(),
-            white_player: name,
-            black_player: contender.name,
-            winner,
-            moves: game.move_list().clone().into_boxed_slice(),
-            board_size: N,
-            misplay: game.is_misplay(),
-        }});
-    let results = white_results.chain(black_results).collect::<Vec<_>>();
+        result.profile = contender_player.profile_report().map(|report| (contender.name, report.to_string()));
+        result
+    });
     {
         let mut write = game_db.write().unwrap();
         for result in &results {
@@ -231,6 +556,11 @@ fn battle_bots<'a, const N: usize>(db: BotDb<'a>, game_db: GameDb<'a>, contender
     {
         let mut write = db.write().unwrap();
         let mut contender = *write.get(contender.name)?; // Update score in case other games have occurred since this bot was created
+        // Elo is updated game by game. For Glicko-2 this batch of games is one
+        // rating period, rated against everyone's ratings from before it.
+        let contender_glicko = contender.glicko();
+        let mut contender_results = Vec::new();
+        let mut opponent_results: HashMap<&str, Vec<(Glicko2, f64)>> = HashMap::new();
         for result in results {
             let mut opponent = *if result.white_player == contender.name {
                 write.get(&result.black_player)
@@ -238,19 +568,34 @@ fn battle_bots<'a, const N: usize>(db: BotDb<'a>, game_db: GameDb<'a>, contender
                 write.get(&result.white_player)
             }?;
 
-            if result.winner == contender.name {
+            let score = if result.winner == contender.name {
                 contender.wins += 1;
                 opponent.losses += 1;
+                1.0
             } else if result.winner == opponent.name {
                 contender.losses += 1;
                 opponent.wins += 1;
+                0.0
             } else {
                 assert!(result.winner == "Tie");
                 contender.ties += 1;
                 opponent.ties += 1;
-            }
+                0.5
+            };
+            let (mut contender_elo, mut opponent_elo) = (Elo { rating: contender.elo }, Elo { rating: opponent.elo });
+            contender_elo.update(&mut opponent_elo, score);
+            contender.elo = contender_elo.rating;
+            opponent.elo = opponent_elo.rating;
+            contender_results.push((opponent.glicko(), score));
+            opponent_results.entry(opponent.name).or_default().push((contender_glicko, 1.0 - score));
             write.insert(opponent.name, opponent);
         }
+        for (name, results) in opponent_results {
+            let mut opponent = *write.get(name)?;
+            opponent.set_glicko(opponent.glicko().updated(&results));
+            write.insert(name, opponent);
+        }
+        contender.set_glicko(contender_glicko.updated(&contender_results));
         write.insert(contender.name, contender);
     } // drop write lock
     Some(())